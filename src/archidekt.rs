@@ -0,0 +1,90 @@
+extern crate serde_derive;
+use async_trait::async_trait;
+use serde::{Deserialize};
+use super::card::Card;
+use super::deck::Deck;
+use super::error::DreadbotError;
+use super::source::{DeckSource, fetch_text};
+
+pub struct Archidekt;
+
+#[derive(Deserialize, Debug)]
+pub struct ArchidektDeck {
+  pub name: Option<String>,
+  pub cards: Vec<ArchidektEntry>
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ArchidektEntry {
+  pub quantity: u32,
+  #[serde(default)]
+  pub categories: Vec<String>,
+  pub card: ArchidektCard
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchidektCard {
+  pub oracle_card: ArchidektOracleCard
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ArchidektOracleCard {
+  pub name: String
+}
+
+impl ArchidektDeck {
+  // Archidekt boards are just categories. The first category is the one the card is filed under.
+  pub fn into_deck(self, id: String) -> Deck {
+    let mut mainboard: Vec<Card> = Vec::new();
+    let mut sideboard: Vec<Card> = Vec::new();
+
+    for entry in self.cards {
+      let card = Card::new(entry.quantity, entry.card.oracle_card.name);
+
+      match entry.categories.first().map(|c| c.as_str()) {
+        Some("Maybeboard") => continue,
+        Some("Sideboard") | Some("Companion") => sideboard.push(card),
+        _ => mainboard.push(card)
+      }
+    }
+
+    let mut deck = Deck::from_cards(id, mainboard, sideboard);
+    if let Some(name) = self.name { deck.set_name(name) }
+    deck
+  }
+}
+
+#[async_trait]
+impl DeckSource for Archidekt {
+  fn name(&self) -> &'static str {
+    "Archidekt"
+  }
+
+  fn url_pattern(&self) -> &'static str {
+    r"^https://(?:www\.)?archidekt\.com/decks/(\d+)"
+  }
+
+  async fn retrieve(&self, id: &str) -> Result<String, DreadbotError> {
+    let uri = format!("https://archidekt.com/api/decks/{}/", id);
+    fetch_text(self, id, &uri).await
+  }
+
+  fn deck_url(&self, id: &str) -> Option<String> {
+    Some(format!("https://archidekt.com/decks/{}", id))
+  }
+
+  fn parse(&self, id: &str, body: &str) -> Result<Deck, DreadbotError> {
+    let archidekt: ArchidektDeck = serde_json::from_str(body)?;
+    archidekt.into_deck(String::from(id)).require_cards()
+  }
+}
+
+#[test]
+fn test_archidekt_fixture() {
+  let deck = Archidekt.parse("55432", include_str!("../fixtures/archidekt.json")).unwrap();
+  let names: Vec<&str> = deck.cards().map(|c| c.name.as_str()).collect();
+
+  assert_eq!(names, vec!["Island", "Treasure Hunt", "Zombie Infestation", "Negate"]);
+  assert_eq!(deck.name(), "Zombie Hunt");
+}
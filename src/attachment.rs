@@ -0,0 +1,87 @@
+use super::card::Card;
+use super::deck::Deck;
+use super::error::DreadbotError;
+use regex::Regex;
+
+const MTGO_CARD_PATTERN: &str = r"<Cards\s([^>]*)>";
+const XML_ATTRIBUTE_PATTERN: &str = r#"(\w+)="([^"]*)""#;
+
+fn decode_xml(value: &str) -> String {
+  value
+    .replace("&quot;", "\"")
+    .replace("&apos;", "'")
+    .replace("&lt;", "<")
+    .replace("&gt;", ">")
+    .replace("&amp;", "&")
+}
+
+// MTGO .dek files are XML with one <Cards> element per entry, flagged with a Sideboard attribute
+pub fn from_mtgo_xml(id: String, body: &str) -> Deck {
+  let card_regex = Regex::new(MTGO_CARD_PATTERN).unwrap();
+  let attribute_regex = Regex::new(XML_ATTRIBUTE_PATTERN).unwrap();
+  let mut mainboard: Vec<Card> = Vec::new();
+  let mut sideboard: Vec<Card> = Vec::new();
+
+  for card_captures in card_regex.captures_iter(body) {
+    let mut quantity: Option<u32> = None;
+    let mut name: Option<String> = None;
+    let mut sideboard_flag = false;
+
+    for attribute in attribute_regex.captures_iter(&card_captures[1]) {
+      match &attribute[1] {
+        "Quantity" => quantity = attribute[2].parse::<u32>().ok(),
+        "Name" => name = Some(decode_xml(&attribute[2])),
+        "Sideboard" => sideboard_flag = &attribute[2] == "true",
+        _ => ()
+      }
+    }
+
+    if let (Some(quantity), Some(name)) = (quantity, name) {
+      let card = Card::new(quantity, name);
+      if sideboard_flag { sideboard.push(card) } else { mainboard.push(card) }
+    }
+  }
+
+  Deck::from_cards(id, mainboard, sideboard)
+}
+
+pub fn parse_attachment(filename: &str, body: &str) -> Result<Deck, DreadbotError> {
+  let extension = filename.rsplit('.').next().unwrap_or("").to_lowercase();
+  let id = String::from(filename);
+
+  match extension.as_str() {
+    "dek" => from_mtgo_xml(id, body).require_cards(),
+    "dec" | "txt" => Deck::parse_text_export(id, body),
+    _ => Err(DreadbotError::Parse { line: None, message: String::from("attachments must be .txt, .dec or .dek files.") })
+  }
+}
+
+#[test]
+fn test_mtgo_dek() {
+  let deck = parse_attachment("zombie_hunt.dek", include_str!("../fixtures/mtgo.dek")).unwrap();
+  let names: Vec<&str> = deck.cards().map(|c| c.name.as_str()).collect();
+
+  assert_eq!(names, vec!["Fire // Ice", "Island", "Treasure Hunt", "Negate"]);
+  assert_eq!(deck.cards().nth(1).unwrap().quantity, 26);
+}
+
+#[test]
+fn test_apprentice_dec() {
+  let deck = parse_attachment("zombie_hunt.dec", include_str!("../fixtures/apprentice.dec")).unwrap();
+  let names: Vec<&str> = deck.cards().map(|c| c.name.as_str()).collect();
+
+  assert_eq!(names, vec!["Island", "Treasure Hunt", "Negate"]);
+}
+
+#[test]
+fn test_arena_txt() {
+  let deck = parse_attachment("zombie_hunt.txt", include_str!("../fixtures/arena.txt")).unwrap();
+  let names: Vec<&str> = deck.cards().map(|c| c.name.as_str()).collect();
+
+  assert_eq!(names, vec!["Island", "Treasure Hunt", "Negate"]);
+}
+
+#[test]
+fn test_unknown_extension() {
+  assert!(parse_attachment("zombie_hunt.png", "").is_err());
+}
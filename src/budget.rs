@@ -0,0 +1,176 @@
+use super::card::{Card, Cents, format_cents};
+use super::names::names_match;
+use super::deck::Deck;
+use super::format::Format;
+use std::collections::BTreeMap;
+
+const MOST_EXPENSIVE_COUNT: usize = 10;
+
+// Groups of cards with identical rules text, so any of them can stand in for another
+const FUNCTIONAL_REPRINTS: &[&[&str]] = &[
+  &["Llanowar Elves", "Fyndhorn Elves", "Elvish Mystic"],
+  &["Savannah Lions", "Elite Vanguard"],
+  &["Grizzly Bears", "Balduvian Bears", "Forest Bear", "Runeclaw Bear"],
+  &["Evolving Wilds", "Terramorphic Expanse"],
+  &["Cultivate", "Kodama's Reach"]
+];
+
+pub fn functional_reprints(name: &str) -> Vec<&'static str> {
+  match FUNCTIONAL_REPRINTS.iter().find(|group| group.iter().any(|card| names_match(card, name))) {
+    Some(group) => group.iter().filter(|card| !names_match(card, name)).cloned().collect(),
+    None => Vec::new()
+  }
+}
+
+// Every functional reprint of a card in the deck, so they can be priced alongside it
+pub fn alternatives_for(deck: &Deck) -> Deck {
+  let mut alternatives: Vec<Card> = Vec::new();
+
+  for card in deck.cards() {
+    for name in functional_reprints(&card.name) {
+      if !alternatives.iter().any(|alternative| alternative.name == name) {
+        alternatives.push(Card::new(1, String::from(name)));
+      }
+    }
+  }
+
+  Deck::from_cards(String::from("alternatives"), alternatives, Vec::new())
+}
+
+fn card_total(card: &Card) -> Cents {
+  card.price.unwrap_or(0).saturating_mul(card.quantity)
+}
+
+// The fewest copies to remove to save at least `over`, taking the priciest copies first
+pub fn fewest_cuts(cards: &[Card], over: Cents) -> Vec<(u32, String)> {
+  let mut copies: Vec<(Cents, &str)> = Vec::new();
+  for card in cards {
+    if let Some(price) = card.price {
+      if price == 0 { continue }
+      for _ in 0..card.quantity { copies.push((price, &card.name)) }
+    }
+  }
+
+  copies.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(b.1)));
+
+  let mut saved: Cents = 0;
+  let mut cuts: BTreeMap<&str, u32> = BTreeMap::new();
+  for (price, name) in copies {
+    if saved >= over { break }
+    saved = saved.saturating_add(price);
+    *cuts.entry(name).or_insert(0) += 1;
+  }
+
+  cuts.into_iter().map(|(name, count)| (count, String::from(name))).collect()
+}
+
+fn board_summary(label: &str, cards: &[Card], total: Cents, limit: Cents) -> String {
+  if total <= limit {
+    return format!("{}: {} of {} ({} remaining)\n", label, format_cents(total), format_cents(limit), format_cents(limit - total));
+  }
+
+  let over = total - limit;
+  let cuts: Vec<String> = fewest_cuts(cards, over).iter()
+    .map(|(count, name)| format!("{} {}", count, name))
+    .collect();
+
+  format!(
+    "{}: {} of {} ({} over)\n  Fewest cuts: {}\n",
+    label, format_cents(total), format_cents(limit), format_cents(over), cuts.join(", ")
+  )
+}
+
+pub fn budget_report(deck: &Deck, format: &Format, alternatives: &Deck) -> String {
+  let mut report = String::from("```\n");
+
+  report += &board_summary("Maindeck", deck.mainboard(), deck.mainboard_pricing(), format.maindeck_limit);
+  report += &board_summary("Sideboard", deck.sideboard(), deck.sideboard_pricing(), format.sideboard_limit);
+
+  let mut ranked: Vec<&Card> = deck.cards().filter(|card| card_total(card) > 0).collect();
+  ranked.sort_by(|a, b| card_total(b).cmp(&card_total(a)).then(a.name.cmp(&b.name)));
+
+  report += "\nMost expensive cards:\n";
+  for card in ranked.iter().take(MOST_EXPENSIVE_COUNT) {
+    report += &format!("{} {} ({})\n", card.quantity, card.name, format_cents(card_total(card)));
+  }
+
+  let mut suggestions: Vec<String> = Vec::new();
+  for card in ranked.iter() {
+    let price = card.price.unwrap_or(0);
+
+    for alternative in alternatives.cards() {
+      let cheaper = match alternative.price {
+        Some(alternative_price) => alternative_price < price,
+        None => false
+      };
+
+      if cheaper && functional_reprints(&card.name).iter().any(|name| names_match(name, &alternative.name)) {
+        suggestions.push(format!(
+          "{} ({} each) can be replaced by {} ({} each)",
+          card.name, format_cents(price), alternative.name, format_cents(alternative.price.unwrap_or(0))
+        ));
+      }
+    }
+
+    if let (Some(set), Some(printing)) = (&card.set, &card.printing) {
+      if !set.eq_ignore_ascii_case(&printing.set) {
+        suggestions.push(format!(
+          "{} is listed as {}, the cheapest printing is {} ({} each)",
+          card.name, set, printing.describe(), format_cents(price)
+        ));
+      }
+    }
+  }
+
+  if !suggestions.is_empty() {
+    report += "\nCheaper options:\n";
+    report += &suggestions.join("\n");
+    report += "\n";
+  }
+
+  report += "```";
+  report
+}
+
+#[test]
+fn test_functional_reprints() {
+  assert_eq!(functional_reprints("Llanowar Elves"), vec!["Fyndhorn Elves", "Elvish Mystic"]);
+  assert!(functional_reprints("Treasure Hunt").is_empty());
+}
+
+#[test]
+fn test_fewest_cuts() {
+  let cards = vec![
+    Card { price: Some(300), ..Card::new(4, String::from("Treasure Hunt")) },
+    Card { price: Some(50), ..Card::new(4, String::from("Zombie Infestation")) },
+    Card { price: Some(0), ..Card::new(26, String::from("Island")) }
+  ];
+
+  assert_eq!(fewest_cuts(&cards, 250), vec![(1, String::from("Treasure Hunt"))]);
+  assert_eq!(fewest_cuts(&cards, 1250), vec![(4, String::from("Treasure Hunt")), (1, String::from("Zombie Infestation"))]);
+}
+
+#[test]
+fn test_budget_report() {
+  let deck_text = "4 Llanowar Elves\r\n4 Treasure Hunt\r\n52 Forest";
+  let mut deck = Deck::from_goldfish_block(String::from("test id"), String::from(deck_text));
+  let mut alternatives = alternatives_for(&deck);
+  let mut format = Format::paper_dreadful();
+  format.maindeck_limit = 10_00;
+
+  for card in deck.cards_mut() {
+    card.price = Some(match card.name.as_str() { "Llanowar Elves" => 200, "Treasure Hunt" => 100, _ => 0 });
+  }
+
+  for card in alternatives.cards_mut() {
+    card.price = Some(if card.name == "Elvish Mystic" { 10 } else { 500 });
+  }
+
+  let report = budget_report(&deck, &format, &alternatives);
+
+  assert!(report.contains("Maindeck: 12.00 of 10.00 (2.00 over)"));
+  assert!(report.contains("Fewest cuts: 1 Llanowar Elves"));
+  assert!(report.contains("Sideboard: 0.00 of 5.00 (5.00 remaining)"));
+  assert!(report.contains("Llanowar Elves (2.00 each) can be replaced by Elvish Mystic (0.10 each)"));
+  assert!(!report.contains("Fyndhorn Elves"));
+}
@@ -0,0 +1,213 @@
+use super::deck::Deck;
+use super::names::normalize;
+#[cfg(test)]
+use super::format::{BasicLandPolicy, PricePolicy};
+use super::scryfall::{PricingSource, ScryfallData, is_paper_printing, reduce_pricing};
+#[cfg(test)]
+use super::scryfall::test_source;
+use regex::Regex;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+// Scryfall names bulk downloads after their updated_at, e.g. default-cards-20191001091527.json
+const BULK_FILE_DATE_PATTERN: &str = r"(\d{4})(\d{2})(\d{2})\d{6}\.json$";
+
+// Cheapest price per card name, built from Scryfall's default_cards bulk data file
+#[derive(Debug)]
+pub struct PriceIndex {
+  entries: Vec<PricingSource>,
+  // Normalized full and front face names to their position in entries
+  keys: HashMap<String, usize>,
+  // Date the bulk data was published, as YYYY-MM-DD. None for indexes built from a snapshot.
+  updated_at: Option<String>
+}
+
+impl PriceIndex {
+  pub fn from_scryfall_data(data: Vec<ScryfallData>) -> Self {
+    let printings: Vec<ScryfallData> = data.into_iter().filter(is_paper_printing).collect();
+    PriceIndex::from_pricing(reduce_pricing(printings))
+  }
+
+  pub fn from_pricing(prices: Vec<PricingSource>) -> Self {
+    let mut keys: HashMap<String, usize> = HashMap::new();
+
+    for (position, entry) in prices.iter().enumerate() {
+      // A full name always wins over another card's front face
+      keys.entry(normalize(&entry.front_name)).or_insert(position);
+      keys.insert(normalize(&entry.name), position);
+    }
+
+    PriceIndex { entries: prices, keys, updated_at: None }
+  }
+
+  pub fn from_file(path: &Path) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+    let reader = BufReader::new(File::open(path)?);
+    let data: Vec<ScryfallData> = serde_json::from_reader(reader)?;
+
+    let mut index = PriceIndex::from_scryfall_data(data);
+    index.updated_at = Some(bulk_updated_at(path)?);
+    Ok(index)
+  }
+
+  pub fn updated_at(&self) -> Option<&str> {
+    self.updated_at.as_deref()
+  }
+
+  pub fn to_pricing(&self) -> Vec<PricingSource> {
+    self.entries.clone()
+  }
+
+  pub fn get(&self, name: &str) -> Option<&PricingSource> {
+    self.keys.get(&normalize(name)).map(|position| &self.entries[*position])
+  }
+
+  // Closest known names by edit distance, for cards that did not match anything. Compares against
+  // the already normalized keys, and only the few close ones are mapped back to display names.
+  pub fn suggestions(&self, name: &str, count: usize) -> Vec<String> {
+    let target = normalize(name);
+    let max_distance = std::cmp::max(2, target.chars().count() / 3);
+
+    let mut candidates: Vec<(usize, &String, usize)> = self.keys.iter()
+      .map(|(key, position)| (edit_distance(&target, key), key, *position))
+      .filter(|(distance, _, _)| *distance <= max_distance)
+      .collect();
+
+    candidates.sort();
+    candidates.into_iter().take(count).map(|(_, key, position)| {
+      let entry = &self.entries[position];
+      if normalize(&entry.name) == *key { entry.name.clone() } else { entry.front_name.clone() }
+    }).collect()
+  }
+
+  // Produces the same entries request_pricing would return for the deck, without the network
+  pub fn pricing_for(&self, deck: &Deck) -> Vec<PricingSource> {
+    deck.cards()
+      .filter_map(|card| self.get(&card.name))
+      .cloned()
+      .collect()
+  }
+}
+
+// The date in Scryfall's file name, or the day the file was last written when it has been renamed
+fn bulk_updated_at(path: &Path) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+  let regex = Regex::new(BULK_FILE_DATE_PATTERN).unwrap();
+  let file_name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+  if let Some(captures) = regex.captures(&file_name) {
+    return Ok(format!("{}-{}-{}", &captures[1], &captures[2], &captures[3]));
+  }
+
+  let modified = fs::metadata(path)?.modified()?.duration_since(UNIX_EPOCH)?;
+  Ok(date_from_days(modified.as_secs() as i64 / 86400))
+}
+
+// Days since 1970-01-01 to a YYYY-MM-DD date in the proleptic Gregorian calendar
+fn date_from_days(days: i64) -> String {
+  let shifted = days + 719_468;
+  let era = shifted.div_euclid(146_097);
+  let day_of_era = shifted.rem_euclid(146_097);
+  let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+  let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+  let month_index = (5 * day_of_year + 2) / 153;
+  let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+  let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+  let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+  format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+  let b_chars: Vec<char> = b.chars().collect();
+  let mut previous: Vec<usize> = (0..=b_chars.len()).collect();
+
+  for (i, a_char) in a.chars().enumerate() {
+    let mut current: Vec<usize> = vec![i + 1];
+
+    for (j, b_char) in b_chars.iter().enumerate() {
+      let substitution = previous[j] + if a_char == *b_char { 0 } else { 1 };
+      current.push(std::cmp::min(substitution, std::cmp::min(previous[j + 1], current[j]) + 1));
+    }
+
+    previous = current;
+  }
+
+  previous[b_chars.len()]
+}
+
+#[test]
+fn test_edit_distance() {
+  assert_eq!(edit_distance("lightning bolt", "lightning bolt"), 0);
+  assert_eq!(edit_distance("lightnig bolt", "lightning bolt"), 1);
+  assert_eq!(edit_distance("kitten", "sitting"), 3);
+}
+
+#[test]
+fn test_bulk_updated_at() {
+  assert_eq!(bulk_updated_at(Path::new("/data/default-cards-20191001091527.json")).unwrap(), "2019-10-01");
+  assert!(bulk_updated_at(Path::new("/missing/default_cards.json")).is_err());
+
+  assert_eq!(date_from_days(0), "1970-01-01");
+  assert_eq!(date_from_days(18_170), "2019-10-01");
+  assert_eq!(date_from_days(19_417), "2023-03-01");
+}
+
+#[test]
+fn test_suggestions() {
+  let data: Vec<ScryfallData> = serde_json::from_str(include_str!("../fixtures/default_cards.json")).unwrap();
+  let index = PriceIndex::from_scryfall_data(data);
+
+  assert_eq!(index.suggestions("Lightnig Blot", 3), vec!["Lightning Bolt"]);
+  assert_eq!(index.suggestions("delver of secret", 3), vec!["Delver of Secrets"]);
+  assert!(index.suggestions("Counterspell", 3).is_empty());
+}
+
+#[test]
+fn test_bulk_filters() {
+  let data: Vec<ScryfallData> = serde_json::from_str(include_str!("../fixtures/default_cards.json")).unwrap();
+  let index = PriceIndex::from_scryfall_data(data);
+
+  // Oversized, digital and gold bordered printings are ignored, so both prices come from the Ice Age bolt
+  let bolt = index.get("Lightning Bolt").unwrap();
+  assert_eq!(bolt.nonfoil.as_ref().unwrap().price, 110);
+  assert_eq!(bolt.foil.as_ref().unwrap().price, 75);
+  assert_eq!(bolt.foil.as_ref().unwrap().describe(), "ICE #213 foil");
+
+  // Cards with no usd price are dropped completely
+  assert!(index.get("Black Lotus").is_none());
+}
+
+#[test]
+fn test_bulk_pricing_for_deck() {
+  let data: Vec<ScryfallData> = serde_json::from_str(include_str!("../fixtures/default_cards.json")).unwrap();
+  let index = PriceIndex::from_scryfall_data(data);
+  let mut deck = Deck::from_goldfish_block(String::from("bulk"), String::from("4 Lightning Bolt\r\n4 Delver of Secrets\r\n4 Black Lotus"));
+
+  assert_eq!(index.get("Delver of Secrets").unwrap().name, "Delver of Secrets // Insectile Aberration");
+  deck.update_pricing(index.pricing_for(&deck), &PricePolicy::default());
+
+  assert_eq!(deck.mainboard_pricing(), 4 * 75 + 4 * 10);
+}
+
+#[test]
+fn test_bulk_pricing_free_basics() {
+  let index = PriceIndex::from_pricing(vec![test_source("Island", Some(10), None), test_source("Treasure Hunt", Some(25), None)]);
+  let mut deck = Deck::from_goldfish_block(String::from("bulk"), String::from("4 Treasure Hunt\r\n56 Island\r\n\r\n2 Island"));
+
+  deck.update_pricing(index.pricing_for(&deck), &PricePolicy::default());
+  assert_eq!(deck.mainboard_pricing(), 4 * 25);
+  assert_eq!(deck.sideboard_pricing(), 0);
+
+  let priced = PricePolicy { basic_lands: BasicLandPolicy::Priced, ..PricePolicy::default() };
+  deck.update_pricing(index.pricing_for(&deck), &priced);
+  assert_eq!(deck.mainboard_pricing(), 4 * 25 + 56 * 10);
+}
+
+#[test]
+fn test_bulk_no_matching_cards() {
+  let index = PriceIndex::from_pricing(vec![test_source("Treasure Hunt", Some(25), None)]);
+  let deck = Deck::from_goldfish_block(String::from("bulk"), String::from("4 Definitely Not A Card"));
+
+  assert!(index.pricing_for(&deck).is_empty());
+}
@@ -0,0 +1,158 @@
+use super::card::Card;
+use super::deck::Deck;
+use super::names::normalize;
+use super::scryfall::PricingSource;
+#[cfg(test)]
+use super::scryfall::test_source;
+use rusqlite::{Connection, OptionalExtension, NO_PARAMS, params};
+use std::collections::HashSet;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Seconds since the epoch, which is what fetched_at is stored as
+pub fn now() -> i64 {
+  SystemTime::now().duration_since(UNIX_EPOCH).map(|elapsed| elapsed.as_secs() as i64).unwrap_or(0)
+}
+
+#[derive(Debug, PartialEq)]
+pub struct CacheStats {
+  pub names: u32,
+  pub stale: u32
+}
+
+// Scryfall entries from live queries, keyed by normalized full and front face name.
+// Freshness is decided by the TTL alone. ETag revalidation is out of scope: one search response
+// covers a whole deck, so its ETag cannot revalidate a single cached card, and revalidating each
+// stale card on its own would cost more requests than fetching them together again.
+pub struct PriceCache {
+  connection: Connection,
+  ttl: i64
+}
+
+impl PriceCache {
+  fn from_connection(connection: Connection, ttl: i64) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+    connection.execute(
+      "CREATE TABLE IF NOT EXISTS price_cache (
+        name TEXT PRIMARY KEY,
+        entry TEXT NOT NULL,
+        fetched_at INTEGER NOT NULL
+      )",
+      NO_PARAMS
+    )?;
+
+    Ok(PriceCache { connection, ttl })
+  }
+
+  pub fn open(path: &Path, ttl: i64) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+    PriceCache::from_connection(Connection::open(path)?, ttl)
+  }
+
+  #[cfg(test)]
+  pub fn open_in_memory(ttl: i64) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+    PriceCache::from_connection(Connection::open_in_memory()?, ttl)
+  }
+
+  pub fn ttl(&self) -> i64 {
+    self.ttl
+  }
+
+  fn get(&self, name: &str, now: i64) -> Result<Option<PricingSource>, Box<dyn std::error::Error + Send + Sync>> {
+    let entry: Option<String> = self.connection.query_row(
+      "SELECT entry FROM price_cache WHERE name = ?1 AND fetched_at > ?2",
+      params![normalize(name), now - self.ttl],
+      |row| row.get(0)
+    ).optional()?;
+
+    match entry {
+      Some(entry) => Ok(Some(serde_json::from_str(&entry)?)),
+      None => Ok(None)
+    }
+  }
+
+  // Fresh entries for the deck, and the cards that still have to be requested
+  pub fn lookup(&self, deck: &Deck, now: i64) -> Result<(Vec<PricingSource>, Vec<Card>), Box<dyn std::error::Error + Send + Sync>> {
+    let mut entries: Vec<PricingSource> = Vec::new();
+    let mut missing: Vec<Card> = Vec::new();
+    let mut seen: HashSet<String> = HashSet::new();
+
+    for card in deck.cards() {
+      if !seen.insert(normalize(&card.name)) { continue }
+
+      match self.get(&card.name, now)? {
+        Some(entry) => entries.push(entry),
+        None => missing.push(card.clone())
+      }
+    }
+
+    Ok((entries, missing))
+  }
+
+  pub fn store(&self, entries: &[PricingSource], now: i64) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    for entry in entries {
+      let serialized = serde_json::to_string(entry)?;
+
+      for name in &[&entry.name, &entry.front_name] {
+        self.connection.execute(
+          "INSERT OR REPLACE INTO price_cache (name, entry, fetched_at) VALUES (?1, ?2, ?3)",
+          params![normalize(name), serialized, now]
+        )?;
+      }
+    }
+
+    Ok(())
+  }
+
+  pub fn stats(&self, now: i64) -> Result<CacheStats, Box<dyn std::error::Error + Send + Sync>> {
+    let (names, stale): (u32, u32) = self.connection.query_row(
+      "SELECT COUNT(*), COUNT(CASE WHEN fetched_at <= ?1 THEN 1 END) FROM price_cache",
+      params![now - self.ttl],
+      |row| Ok((row.get(0)?, row.get(1)?))
+    )?;
+
+    Ok(CacheStats { names, stale })
+  }
+
+  // Drops every cached entry and returns how many names were removed
+  pub fn flush(&self) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+    Ok(self.connection.execute("DELETE FROM price_cache", NO_PARAMS)?)
+  }
+}
+
+#[test]
+fn test_lookup_fresh_and_missing() {
+  let cache = PriceCache::open_in_memory(3600).unwrap();
+  let mut delver = test_source("Delver of Secrets // Insectile Aberration", Some(10), None);
+  delver.front_name = String::from("Delver of Secrets");
+  cache.store(&[delver, test_source("Treasure Hunt", Some(25), None)], 1000).unwrap();
+
+  let deck = Deck::from_goldfish_block(String::from("cache"), String::from("4 Delver of Secrets\r\n4 Treasure Hunt\r\n4 Negate\r\n\r\n2 Negate"));
+  let (entries, missing) = cache.lookup(&deck, 2000).unwrap();
+
+  let names: Vec<&str> = entries.iter().map(|entry| entry.name.as_str()).collect();
+  assert_eq!(names, vec!["Delver of Secrets // Insectile Aberration", "Treasure Hunt"]);
+  assert_eq!(missing.len(), 1);
+  assert_eq!(missing[0].name, "Negate");
+}
+
+#[test]
+fn test_stale_entries() {
+  let cache = PriceCache::open_in_memory(3600).unwrap();
+  cache.store(&[test_source("Treasure Hunt", Some(25), None)], 1000).unwrap();
+  cache.store(&[test_source("Negate", Some(10), None)], 4000).unwrap();
+
+  let deck = Deck::from_goldfish_block(String::from("cache"), String::from("4 Treasure Hunt\r\n4 Negate"));
+  let (entries, missing) = cache.lookup(&deck, 5000).unwrap();
+
+  assert_eq!(entries[0].name, "Negate");
+  assert_eq!(missing[0].name, "Treasure Hunt");
+  assert_eq!(cache.stats(5000).unwrap(), CacheStats { names: 2, stale: 1 });
+}
+
+#[test]
+fn test_flush() {
+  let cache = PriceCache::open_in_memory(3600).unwrap();
+  cache.store(&[test_source("Treasure Hunt", Some(25), None)], 1000).unwrap();
+
+  assert_eq!(cache.flush().unwrap(), 1);
+  assert_eq!(cache.stats(1000).unwrap(), CacheStats { names: 0, stale: 0 });
+}
@@ -1,5 +1,49 @@
+extern crate serde_derive;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use super::names::{display_name, names_match};
+
 pub type Cents = u32;
 
+const BASIC_LAND_NAMES: &[&str] = &[
+  "Swamp",
+  "Island",
+  "Forest",
+  "Mountain",
+  "Plains"
+];
+
+// Cards that override the copy limit, with the number of copies they allow. None means any number.
+const COPY_LIMIT_EXCEPTIONS: &[(&str, Option<u32>)] = &[
+  ("Plains", None),
+  ("Island", None),
+  ("Swamp", None),
+  ("Mountain", None),
+  ("Forest", None),
+  ("Wastes", None),
+  ("Snow-Covered Plains", None),
+  ("Snow-Covered Island", None),
+  ("Snow-Covered Swamp", None),
+  ("Snow-Covered Mountain", None),
+  ("Snow-Covered Forest", None),
+  ("Snow-Covered Wastes", None),
+  ("Relentless Rats", None),
+  ("Rat Colony", None),
+  ("Persistent Petitioners", None),
+  ("Shadowborn Apostle", None),
+  ("Dragon's Approach", None),
+  ("Slime Against Humanity", None),
+  ("Hare Apparent", None),
+  ("Templar Knight", None),
+  ("Tempest Hawk", None),
+  ("Cid, Timeless Artificer", None),
+  ("Seven Dwarves", Some(7)),
+  ("Nazgûl", Some(9))
+];
+
+const ARENA_LINE_PATTERN: &str = r"^(\d+)x?\s+(.+?)(?:\s+\(([A-Za-z0-9]+)\)(?:\s+(\S+))?)?$";
+
 pub fn format_cents(amount: Cents) -> String {
   let dollars = amount / 100;
   let remainder = amount % 100;
@@ -7,39 +51,188 @@ pub fn format_cents(amount: Cents) -> String {
   format!("{}.{:02}", dollars, remainder)
 }
 
-#[derive(Debug)]
+// Parses prices like "0.29" or "12" exactly. Floats would turn "0.29" into 28 cents.
+pub fn parse_cents(amount: &str) -> Option<Cents> {
+  let mut parts = amount.trim().splitn(2, '.');
+  let dollars = parts.next()?;
+  let remainder = parts.next().unwrap_or("");
+
+  if dollars.is_empty() || remainder.len() > 2 { return None }
+  if !dollars.chars().chain(remainder.chars()).all(|c| c.is_ascii_digit()) { return None }
+
+  let remainder_cents = match remainder.len() {
+    0 => 0,
+    1 => remainder.parse::<Cents>().ok()? * 10,
+    _ => remainder.parse::<Cents>().ok()?
+  };
+
+  dollars.parse::<Cents>().ok()?.checked_mul(100)?.checked_add(remainder_cents)
+}
+
+// The printing a card's price was taken from
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Printing {
+  pub price: Cents,
+  pub set: String,
+  pub collector_number: String,
+  pub scryfall_uri: String,
+  pub foil: bool
+}
+
+impl Printing {
+  pub fn describe(&self) -> String {
+    format!("{} #{} {}", self.set, self.collector_number, if self.foil { "foil" } else { "nonfoil" })
+  }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Card {
   pub quantity: u32,
   pub name: String,
-  pub price: Option<Cents>
+  // The name as the list spelled it, which hashes are computed from. None for submissions
+  // recorded before it was kept.
+  #[serde(default)]
+  pub listed_name: Option<String>,
+  pub set: Option<String>,
+  pub collector_number: Option<String>,
+  pub price: Option<Cents>,
+  #[serde(default)]
+  pub printing: Option<Printing>,
+  // Filled in from Scryfall along with the price
+  #[serde(skip)]
+  pub legalities: HashMap<String, String>
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Section {
+  Deck,
+  Sideboard,
+  Companion,
+  Commander
+}
+
+impl Section {
+  pub fn from_header(line: &str) -> Option<Self> {
+    match line.trim().trim_end_matches(':').to_lowercase().as_str() {
+      "deck" | "main" | "maindeck" | "mainboard" => Some(Section::Deck),
+      "sideboard" => Some(Section::Sideboard),
+      "companion" => Some(Section::Companion),
+      "commander" => Some(Section::Commander),
+      _ => None
+    }
+  }
+
+  // Commanders are played from the command zone and count towards the maindeck. Companions
+  // start the game outside of it, so they are treated as sideboard cards.
+  pub fn is_sideboard(&self) -> bool {
+    match self {
+      Section::Sideboard | Section::Companion => true,
+      Section::Deck | Section::Commander => false
+    }
+  }
 }
 
 impl Card {
+  pub fn new(quantity: u32, name: String) -> Self {
+    Card {
+      quantity,
+      name: display_name(&name),
+      listed_name: Some(name.replace("/", " // ")),
+      set: None,
+      collector_number: None,
+      price: None,
+      printing: None,
+      legalities: HashMap::new()
+    }
+  }
+
+  // Normalizing names must not change hashes that were already handed out, so they are computed
+  // from the name as listed, with "/" spaced out the way the original parser did
+  pub fn hash_name(&self) -> &str {
+    self.listed_name.as_ref().unwrap_or(&self.name)
+  }
+
   pub fn from_goldfish_line(line: &str) -> Option<Self> {
     if line.is_empty() { return None }
 
-    let mut splitter = line.splitn(2, " ");
-    let quantity_string = splitter.next().unwrap();
-    let name_string = splitter.next().unwrap();
+    let (quantity_string, name_string) = line.split_once(' ')?;
     let quantity_parsed = quantity_string.parse::<u32>();
 
     match quantity_parsed {
-      Ok(quantity) => Some(Card {
-        quantity: quantity,
-        name: String::from(name_string).replace("/", " // "),
-        price: None
-      }),
+      Ok(quantity) => Some(Card::new(quantity, String::from(name_string))),
       Err(_) => None
     }
   }
 
+  // Arena exports look like "4 Lightning Bolt (M10) 146". The set and collector number are optional.
+  pub fn from_arena_line(line: &str) -> Option<Self> {
+    let regex = Regex::new(ARENA_LINE_PATTERN).unwrap();
+    let captures = regex.captures(line.trim())?;
+    let quantity = captures[1].parse::<u32>().ok()?;
+
+    let mut card = Card::new(quantity, String::from(&captures[2]));
+    card.set = captures.get(3).map(|c| c.as_str().to_uppercase());
+    card.collector_number = captures.get(4).map(|c| String::from(c.as_str()));
+
+    Some(card)
+  }
+
+  pub fn is_basic_land(&self) -> bool {
+    BASIC_LAND_NAMES.iter().any(|name| names_match(name, &self.name))
+  }
+
+  // How many copies of this card a deck may hold, or None when any number is allowed
+  pub fn copy_limit(&self, default_limit: u32) -> Option<u32> {
+    match COPY_LIMIT_EXCEPTIONS.iter().find(|(name, _)| names_match(name, &self.name)) {
+      Some((_, limit)) => *limit,
+      None => Some(default_limit)
+    }
+  }
+
+  // The Scryfall status of this card in a format, when it is anything but legal
+  pub fn illegal_status(&self, format: &str) -> Option<String> {
+    match self.legalities.get(format).map(|status| status.as_str()) {
+      Some("legal") | None => None,
+      Some(status) => Some(status.replace("_", " "))
+    }
+  }
+
   pub fn info_string(&self) -> String {
-    match &self.price {
-      Some(amount) => format!(
+    match (&self.price, &self.printing) {
+      (Some(amount), Some(printing)) => format!(
+        "{} {} ({} each, {} total) {}",
+        self.quantity, self.name, format_cents(*amount), format_cents(amount.saturating_mul(self.quantity)), printing.describe()
+      ),
+      (Some(amount), None) => format!(
         "{} {} ({} each, {} total)",
-        self.quantity, self.name, format_cents(*amount), format_cents(*amount * self.quantity)
+        self.quantity, self.name, format_cents(*amount), format_cents(amount.saturating_mul(self.quantity))
       ),
-      None => format!("{} {} (unpriced)", self.quantity, self.name)
+      (None, _) => format!("{} {} (unpriced)", self.quantity, self.name)
+    }
+  }
+}
+
+#[test]
+fn test_parse_cents() {
+  assert_eq!(parse_cents("0.29"), Some(29));
+  assert_eq!(parse_cents("20.00"), Some(2000));
+  assert_eq!(parse_cents("1.5"), Some(150));
+  assert_eq!(parse_cents("7"), Some(700));
+  assert_eq!(parse_cents("42949672.95"), Some(Cents::MAX));
+  assert_eq!(parse_cents("42949672.96"), None);
+  assert_eq!(parse_cents("0.299"), None);
+  assert_eq!(parse_cents("-1.00"), None);
+  assert_eq!(parse_cents(".50"), None);
+  assert_eq!(parse_cents(""), None);
+}
+
+#[test]
+fn test_parse_cents_every_two_decimal_string() {
+  for dollars in 0..10000 {
+    for remainder in 0..100 {
+      let amount = format!("{}.{:02}", dollars, remainder);
+      assert_eq!(parse_cents(&amount), Some(dollars * 100 + remainder), "{}", amount);
+      assert_eq!(format_cents(dollars * 100 + remainder), amount);
     }
   }
 }
@@ -54,7 +247,13 @@ fn test_card_creation() {
 #[test]
 fn test_empty_card() {
   let card = Card::from_goldfish_line("");
-  assert_eq!(card.is_none(), true);
+  assert!(card.is_none());
+}
+
+#[test]
+fn test_header_line() {
+  let card = Card::from_goldfish_line("Sideboard:");
+  assert!(card.is_none());
 }
 
 #[test]
@@ -63,3 +262,62 @@ fn test_parses_split_card() {
   assert_eq!(card.name, "Fire // Ice");
   assert_eq!(card.quantity, 4);
 }
+
+#[test]
+fn test_copy_limit() {
+  assert_eq!(Card::new(4, String::from("Treasure Hunt")).copy_limit(4), Some(4));
+  assert_eq!(Card::new(30, String::from("Relentless Rats")).copy_limit(4), None);
+  assert_eq!(Card::new(20, String::from("Snow-Covered Island")).copy_limit(4), None);
+  assert_eq!(Card::new(7, String::from("Seven Dwarves")).copy_limit(4), Some(7));
+}
+
+#[test]
+fn test_info_string_printing() {
+  let card = Card {
+    price: Some(40),
+    printing: Some(Printing {
+      price: 40,
+      set: String::from("MMQ"),
+      collector_number: String::from("123"),
+      scryfall_uri: String::new(),
+      foil: false
+    }),
+    ..Card::new(4, String::from("Lightning Bolt"))
+  };
+
+  assert_eq!(card.info_string(), "4 Lightning Bolt (0.40 each, 1.60 total) MMQ #123 nonfoil");
+  assert_eq!(Card::new(4, String::from("Lightning Bolt")).info_string(), "4 Lightning Bolt (unpriced)");
+}
+
+#[test]
+fn test_parses_arena_line() {
+  let card = Card::from_arena_line("4 Lightning Bolt (M10) 146").unwrap();
+  assert_eq!(card.name, "Lightning Bolt");
+  assert_eq!(card.quantity, 4);
+  assert_eq!(card.set, Some(String::from("M10")));
+  assert_eq!(card.collector_number, Some(String::from("146")));
+}
+
+#[test]
+fn test_parses_arena_line_without_printing() {
+  let card = Card::from_arena_line("1 Fire // Ice").unwrap();
+  assert_eq!(card.name, "Fire // Ice");
+  assert_eq!(card.set, None);
+  assert_eq!(card.collector_number, None);
+}
+
+#[test]
+fn test_parses_arena_split_card() {
+  let card = Card::from_arena_line("2 Fire/Ice (MH2) 290").unwrap();
+  assert_eq!(card.name, "Fire // Ice");
+  assert_eq!(card.set, Some(String::from("MH2")));
+}
+
+#[test]
+fn test_section_headers() {
+  assert_eq!(Section::from_header("Deck"), Some(Section::Deck));
+  assert_eq!(Section::from_header("Sideboard:"), Some(Section::Sideboard));
+  assert_eq!(Section::from_header("Companion"), Some(Section::Companion));
+  assert_eq!(Section::from_header("commander"), Some(Section::Commander));
+  assert_eq!(Section::from_header("4 Island"), None);
+}
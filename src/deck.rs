@@ -1,21 +1,85 @@
-use super::card::{Card, Cents};
+use super::card::{Card, Cents, Section};
+use super::error::DreadbotError;
+use super::names::{names_match, normalize};
+use super::format::{BasicLandPolicy, DeckRules, PricePolicy};
 use super::scryfall::{PricingSource};
+#[cfg(test)]
+use super::scryfall::test_source;
+use regex::Regex;
 use sha2::{Sha256, Digest};
+use std::collections::BTreeMap;
+
+const DEC_SET_PATTERN: &str = r"\[[A-Za-z0-9]*\]\s*";
+
+// Fails on the first line that is not blank, a comment, a section header or a card
+fn check_lines<F: Fn(&str) -> bool>(block: &str, is_card: F) -> Result<(), DreadbotError> {
+  for (number, raw_line) in block.trim_start_matches('\u{feff}').lines().enumerate() {
+    let line = raw_line.trim();
+    if line.is_empty() || line.starts_with("//") || Section::from_header(line).is_some() || is_card(line) { continue }
+
+    return Err(DreadbotError::Parse { line: Some(number + 1), message: format!("{:?} is not a card.", line) });
+  }
+
+  Ok(())
+}
+
+// A card's name as first listed, its copies across both boards, its copy limit and its base format status
+type CopyCount<'a> = (&'a str, u32, Option<u32>, Option<String>);
 
 #[derive(Debug)]
 pub struct Deck {
-  goldfish_id: String,
+  id: String,
+  name: Option<String>,
+  source: Option<String>,
   mainboard: Vec<Card>,
   sideboard: Vec<Card>
 }
 
 impl Deck {
+  pub fn from_cards(id: String, mut mainboard: Vec<Card>, mut sideboard: Vec<Card>) -> Self {
+    mainboard.sort_by(|a, b| a.name.cmp(&b.name));
+    sideboard.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Deck {
+      id,
+      name: None,
+      source: None,
+      mainboard,
+      sideboard
+    }
+  }
+
+  pub fn set_name(&mut self, name: String) {
+    self.name = Some(name);
+  }
+
+  // The deck's title on its site, falling back to its id
+  pub fn name(&self) -> &str {
+    self.name.as_ref().unwrap_or(&self.id)
+  }
+
+  pub fn set_source(&mut self, source: String) {
+    self.source = Some(source);
+  }
+
+  pub fn source(&self) -> Option<&str> {
+    self.source.as_deref()
+  }
+
+  pub fn mainboard(&self) -> &Vec<Card> {
+    &self.mainboard
+  }
+
+  pub fn sideboard(&self) -> &Vec<Card> {
+    &self.sideboard
+  }
+
   pub fn from_goldfish_block(goldfish_id: String, block: String) -> Self {
     let mut mainboard: Vec<Card> = Vec::new();
     let mut sideboard: Vec<Card> = Vec::new();
     let mut sideboard_flag = false;
 
-    for line in block.split("\r\n") {
+    for line in block.lines() {
         match Card::from_goldfish_line(line) {
             Some(card) => {
               if sideboard_flag { sideboard.push(card) } else { mainboard.push(card) }
@@ -24,30 +88,111 @@ impl Deck {
         };
     }
 
-    mainboard.sort_by(|a, b| a.name.cmp(&b.name));
-    sideboard.sort_by(|a, b| a.name.cmp(&b.name));
+    Deck::from_cards(goldfish_id, mainboard, sideboard)
+  }
 
-    Deck {
-      goldfish_id: goldfish_id,
-      mainboard: mainboard,
-      sideboard: sideboard
+  // Like from_goldfish_block, but rejects lines that are not cards and lists with no cards
+  pub fn parse_goldfish_block(goldfish_id: String, block: String) -> Result<Self, DreadbotError> {
+    check_lines(&block, |line| Card::from_goldfish_line(line).is_some())?;
+    Deck::from_goldfish_block(goldfish_id, block).require_cards()
+  }
+
+  // Handles Arena exports with section headers and "(SET) 123" printings, as well as .dec files
+  // with "SB:" prefixes, "[SET]" tags and "//" comments. A blank line after the maindeck starts
+  // the sideboard, the same as in a Goldfish download.
+  pub fn from_text_export(id: String, block: &str) -> Self {
+    let dec_set_regex = Regex::new(DEC_SET_PATTERN).unwrap();
+    let mut mainboard: Vec<Card> = Vec::new();
+    let mut sideboard: Vec<Card> = Vec::new();
+    let mut section = Section::Deck;
+
+    for raw_line in block.trim_start_matches('\u{feff}').lines() {
+      let line = raw_line.trim();
+      if line.starts_with("//") { continue }
+
+      if let Some(header) = Section::from_header(line) {
+        section = header;
+        continue;
+      }
+
+      let (line, sideboard_line) = match line.strip_prefix("SB:") {
+        Some(rest) => (rest.trim(), true),
+        None => (line, section.is_sideboard())
+      };
+
+      match Card::from_arena_line(&dec_set_regex.replace(line, "")) {
+        Some(card) => {
+          if sideboard_line { sideboard.push(card) } else { mainboard.push(card) }
+        },
+        None => {
+          if section == Section::Deck && !mainboard.is_empty() { section = Section::Sideboard }
+        }
+      }
     }
+
+    Deck::from_cards(id, mainboard, sideboard)
   }
 
-  fn update_card_pricing(card: &mut Card, entry: &PricingSource) {
-    if card.name == entry.front_name || card.name == entry.name {
-      card.price = Some(entry.price);
+  // Like from_text_export, but rejects lines that are not cards and lists with no cards
+  pub fn parse_text_export(id: String, block: &str) -> Result<Self, DreadbotError> {
+    let dec_set_regex = Regex::new(DEC_SET_PATTERN).unwrap();
+    check_lines(block, |line| {
+      let line = line.strip_prefix("SB:").map(str::trim).unwrap_or(line);
+      Card::from_arena_line(&dec_set_regex.replace(line, "")).is_some()
+    })?;
+
+    Deck::from_text_export(id, block).require_cards()
+  }
+
+  pub fn require_cards(self) -> Result<Self, DreadbotError> {
+    if self.cards().next().is_none() {
+      return Err(DreadbotError::Parse { line: None, message: String::from("the list has no cards.") });
     }
+
+    Ok(self)
+  }
+
+  // Decks with cards no price source knows about should not be issued a hash
+  pub fn require_matched(&self) -> Result<(), DreadbotError> {
+    let unmatched = self.unmatched_cards();
+    if unmatched.is_empty() { return Ok(()) }
+
+    Err(DreadbotError::UnmatchedCards(unmatched.iter().map(|card| card.name.clone()).collect()))
+  }
+
+  fn update_card_pricing(card: &mut Card, entry: &PricingSource, policy: &PricePolicy) {
+    // Free basics keep their zero price even when the entries come from bulk data or a snapshot
+    if card.is_basic_land() && policy.basic_lands == BasicLandPolicy::Free { return }
+
+    let canonical = if names_match(&card.name, &entry.name) {
+      &entry.name
+    } else if names_match(&card.name, &entry.front_name) {
+      &entry.front_name
+    } else {
+      return
+    };
+
+    // Scryfall's spelling, so differently spelled copies are reported under one name
+    card.name = canonical.clone();
+    card.printing = entry.printing(policy).cloned();
+    card.price = card.printing.as_ref().map(|printing| printing.price);
+    card.legalities = entry.legalities.clone();
   }
 
-  pub fn update_pricing(&mut self, scryfall_entries: Vec<PricingSource>) {
+  pub fn update_pricing(&mut self, scryfall_entries: Vec<PricingSource>, policy: &PricePolicy) {
+    if policy.basic_lands == BasicLandPolicy::Free {
+      for card in self.cards_mut() {
+        if card.is_basic_land() { card.price = Some(0) }
+      }
+    }
+
     for entry in scryfall_entries {
       for card in &mut self.mainboard {
-        Self::update_card_pricing(card, &entry);
+        Self::update_card_pricing(card, &entry, policy);
       }
 
       for card in &mut self.sideboard {
-        Self::update_card_pricing(card, &entry);
+        Self::update_card_pricing(card, &entry, policy);
       }
     }
   }
@@ -56,17 +201,16 @@ impl Deck {
     DeckIter { deck: self, index: 0 }
   }
 
-  fn sum_prices(cards: &Vec<Card>) -> Cents {
-    let mut total_cents: Cents = 0;
-
-    for card in cards {
-      total_cents = match &card.price {
-        Some(amount) => total_cents + card.quantity * *amount,
-        None => total_cents
-      };
-    }
+  pub fn cards_mut(&mut self) -> impl Iterator<Item = &mut Card> {
+    self.mainboard.iter_mut().chain(self.sideboard.iter_mut())
+  }
 
-    total_cents
+  // Saturates at Cents::MAX on overflow, so a huge quantity can never slip under a limit
+  fn sum_prices(cards: &[Card]) -> Cents {
+    cards.iter()
+      .filter_map(|card| card.price.map(|amount| (amount, card.quantity)))
+      .try_fold(0, |total: Cents, (amount, quantity)| amount.checked_mul(quantity)?.checked_add(total))
+      .unwrap_or(Cents::MAX)
   }
 
   pub fn mainboard_pricing(&self) -> Cents {
@@ -98,26 +242,188 @@ impl Deck {
     info
   }
 
-  pub fn to_hash(&self) -> String {
+  // Cards that were not matched to any Scryfall entry. Their price is unknown, not zero.
+  pub fn unmatched_cards(&self) -> Vec<&Card> {
+    let mut unmatched: Vec<&Card> = Vec::new();
+
+    for card in self.cards() {
+      if card.price.is_none() && !unmatched.iter().any(|other| other.name == card.name) {
+        unmatched.push(card);
+      }
+    }
+
+    unmatched
+  }
+
+  // Saturates like sum_prices, so a huge quantity cannot wrap around under a limit
+  fn card_count(cards: &[Card]) -> u32 {
+    cards.iter().fold(0, |total: u32, card| total.saturating_add(card.quantity))
+  }
+
+  // Deck construction problems, independent of price
+  pub fn legality_violations(&self, rules: &DeckRules) -> Vec<String> {
+    let mut violations: Vec<String> = Vec::new();
+
+    let mainboard_count = Deck::card_count(&self.mainboard);
+    if mainboard_count < rules.min_mainboard {
+      violations.push(format!(
+        "Mainboard has {} cards, at least {} are required.", mainboard_count, rules.min_mainboard
+      ));
+    }
+
+    let sideboard_count = Deck::card_count(&self.sideboard);
+    if sideboard_count > rules.max_sideboard {
+      violations.push(format!(
+        "Sideboard has {} cards, at most {} are allowed.", sideboard_count, rules.max_sideboard
+      ));
+    }
+
+    // Copies are counted across both boards, and a card may be listed on several lines
+    // Grouped by normalized name, so differently spelled copies of a card count together
+    let mut copies: BTreeMap<String, CopyCount<'_>> = BTreeMap::new();
+    for card in self.cards() {
+      let limit = if rules.is_restricted(&card.name) { Some(1) } else { card.copy_limit(rules.max_copies) };
+      let base_status = rules.base_format.as_ref().and_then(|base_format| card.illegal_status(base_format));
+      let entry = copies.entry(normalize(&card.name)).or_insert((&card.name, 0, limit, base_status));
+      entry.1 = entry.1.saturating_add(card.quantity);
+    }
+
+    for (_, (name, count, limit, base_status)) in copies {
+      if rules.is_banned(name) {
+        violations.push(format!("{} is banned.", name));
+        continue;
+      }
+
+      if let (Some(status), Some(base_format)) = (base_status, &rules.base_format) {
+        violations.push(format!("{} is {} in {}.", name, status, base_format));
+      }
+
+      if let Some(limit) = limit {
+        if count > limit {
+          violations.push(format!("{} copies of {}, at most {} are allowed.", count, name, limit));
+        }
+      }
+    }
+
+    violations
+  }
+
+  fn board_diff(label: &str, original: &[Card], current: &[Card]) -> Vec<String> {
+    let mut counts: BTreeMap<&str, i64> = BTreeMap::new();
+
+    for card in original {
+      *counts.entry(&card.name).or_insert(0) -= card.quantity as i64;
+    }
+
+    for card in current {
+      *counts.entry(&card.name).or_insert(0) += card.quantity as i64;
+    }
+
+    counts.iter()
+      .filter(|(_, count)| **count != 0)
+      .map(|(name, count)| format!("{}: {:+} {}", label, count, name))
+      .collect()
+  }
+
+  // Card level changes needed to turn the original deck into this one
+  pub fn diff(&self, original: &Deck) -> Vec<String> {
+    let mut changes = Deck::board_diff("Mainboard", &original.mainboard, &self.mainboard);
+    changes.append(&mut Deck::board_diff("Sideboard", &original.sideboard, &self.sideboard));
+    changes
+  }
+
+  // Boards are sorted by display name, but hashes were issued with cards sorted as listed
+  fn hash_order(cards: &[Card]) -> Vec<&Card> {
+    let mut ordered: Vec<&Card> = cards.iter().collect();
+    ordered.sort_by(|a, b| a.hash_name().cmp(b.hash_name()));
+    ordered
+  }
+
+  // The original scheme. It hashes the mainboard twice and never the sideboard, so it is only
+  // kept around to check hashes that were handed out before v2.
+  fn legacy_hash(&self) -> String {
+    let mainboard = Deck::hash_order(&self.mainboard);
     let mut hasher = Sha256::new();
 
-    for card in &self.mainboard {
-      hasher.input(format!("#{} {}", card.quantity, card.name));
+    for card in &mainboard {
+      hasher.input(format!("#{} {}", card.quantity, card.hash_name()));
     }
 
     hasher.input("||");
 
-    for card in &self.mainboard {
-      hasher.input(format!("#{} {}", card.quantity, card.name));
+    for card in &mainboard {
+      hasher.input(format!("#{} {}", card.quantity, card.hash_name()));
     }
 
-    let mut hash_string = String::new();
-    for byte in hasher.result()[..6].iter() {
-      hash_string += &format!("{:02X}", byte);
+    hex_prefix(&hasher.result()[..6])
+  }
+
+  fn v2_hash(&self) -> String {
+    let mut hasher = Sha256::new();
+
+    hasher.input("dreadbot-hash-v2");
+
+    for card in Deck::hash_order(&self.mainboard) {
+      hasher.input(format!("#{} {}", card.quantity, card.hash_name()));
+    }
+
+    hasher.input("||");
+
+    for card in Deck::hash_order(&self.sideboard) {
+      hasher.input(format!("#{} {}", card.quantity, card.hash_name()));
+    }
+
+    format!("v2-{}", hex_prefix(&hasher.result()[..6]))
+  }
+
+  pub fn to_versioned_hash(&self, version: HashVersion) -> String {
+    match version {
+      HashVersion::V1 => self.legacy_hash(),
+      HashVersion::V2 => self.v2_hash()
     }
+  }
+
+  pub fn to_hash(&self) -> String {
+    self.to_versioned_hash(HashVersion::V2)
+  }
+
+  // Recomputes the hash with whichever scheme the given hash was issued under
+  pub fn matches_hash(&self, hash: &str) -> bool {
+    let hash = normalize_hash(hash);
+    self.to_versioned_hash(HashVersion::of(&hash)) == hash
+  }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum HashVersion {
+  V1,
+  V2
+}
+
+impl HashVersion {
+  // v1 hashes were issued without a prefix
+  pub fn of(hash: &str) -> Self {
+    if hash.to_lowercase().starts_with("v2-") { HashVersion::V2 } else { HashVersion::V1 }
+  }
+}
+
+// Players type hashes back in by hand, so accept any case for both the prefix and the digits
+pub fn normalize_hash(hash: &str) -> String {
+  let hash = hash.trim();
+
+  match HashVersion::of(hash) {
+    HashVersion::V1 => hash.to_uppercase(),
+    HashVersion::V2 => format!("v2-{}", hash[3..].to_uppercase())
+  }
+}
 
-    hash_string
+fn hex_prefix(bytes: &[u8]) -> String {
+  let mut hash_string = String::new();
+  for byte in bytes.iter() {
+    hash_string += &format!("{:02X}", byte);
   }
+
+  hash_string
 }
 
 pub struct DeckIter<'a> {
@@ -153,14 +459,38 @@ fn test_deck_creation() {
   let id = "test id";
   let deck = Deck::from_goldfish_block(String::from(id), String::from(deck_text));
 
-  assert_eq!(deck.goldfish_id, String::from(id));
+  assert_eq!(deck.id, String::from(id));
   assert_eq!(deck.mainboard.len(), 4);
-  assert_eq!(deck.mainboard.get(0).unwrap().quantity, 26);
-  assert_eq!(deck.mainboard.get(0).unwrap().name, "Island");
+  assert_eq!(deck.mainboard.first().unwrap().quantity, 26);
+  assert_eq!(deck.mainboard.first().unwrap().name, "Island");
 
   assert_eq!(deck.sideboard.len(), 1);
-  assert_eq!(deck.sideboard.get(0).unwrap().quantity, 15);
-  assert_eq!(deck.sideboard.get(0).unwrap().name, "Good Sideboard Card");
+  assert_eq!(deck.sideboard.first().unwrap().quantity, 15);
+  assert_eq!(deck.sideboard.first().unwrap().name, "Good Sideboard Card");
+}
+
+#[test]
+fn test_arena_export() {
+  let deck_text = "Companion\n1 Lurrus of the Dream-Den (IKO) 226\n\nDeck\n4 Lightning Bolt (M10) 146\n20 Mountain (THB) 285\n\nSideboard\n3 Negate (M20) 69";
+  let deck = Deck::from_text_export(String::from("arena"), deck_text);
+
+  assert_eq!(deck.mainboard.len(), 2);
+  assert_eq!(deck.mainboard.first().unwrap().name, "Lightning Bolt");
+  assert_eq!(deck.mainboard.first().unwrap().set, Some(String::from("M10")));
+  assert_eq!(deck.mainboard.first().unwrap().collector_number, Some(String::from("146")));
+
+  assert_eq!(deck.sideboard.len(), 2);
+  assert_eq!(deck.sideboard.first().unwrap().name, "Lurrus of the Dream-Den");
+  assert_eq!(deck.sideboard.get(1).unwrap().name, "Negate");
+}
+
+#[test]
+fn test_arena_commander() {
+  let deck_text = "Commander\n1 Zombie Infestation (ODY) 174\n\nDeck\n4 Treasure Hunt (EXO) 50";
+  let deck = Deck::from_text_export(String::from("arena"), deck_text);
+
+  assert_eq!(deck.mainboard.len(), 2);
+  assert_eq!(deck.sideboard.len(), 0);
 }
 
 #[test]
@@ -174,7 +504,7 @@ fn test_iterator() {
   assert_eq!(deck_iter.next().unwrap().name, "Treasure Hunt");
   assert_eq!(deck_iter.next().unwrap().name, "Zombie Infestation");
   assert_eq!(deck_iter.next().unwrap().name, "Island");
-  assert_eq!(deck_iter.next().is_none(), true);
+  assert!(deck_iter.next().is_none());
 }
 
 #[test]
@@ -183,32 +513,54 @@ fn test_pricing_update() {
   let id = "test id";
   let mut deck = Deck::from_goldfish_block(String::from(id), String::from(deck_text));
 
-  let mut scryfall_entries: Vec<PricingSource> = Vec::new();
-  scryfall_entries.push(PricingSource {
-    name: String::from("Island"),
-    front_name: String::from("Island"),
-    price: 100
-  });
+  let scryfall_entries: Vec<PricingSource> = vec![test_source("Island", Some(100), None)];
 
-  deck.update_pricing(scryfall_entries);
+  let policy = PricePolicy { basic_lands: BasicLandPolicy::Priced, ..PricePolicy::default() };
+  deck.update_pricing(scryfall_entries, &policy);
 
-  let island = deck.mainboard.get(0).unwrap();
+  let island = deck.mainboard.first().unwrap();
   assert_eq!(island.price, Some(100));
+  assert_eq!(island.printing.as_ref().unwrap().describe(), "TST #1 nonfoil");
 
   let treasure_hunt = deck.mainboard.get(1).unwrap();
   assert_eq!(treasure_hunt.price, None);
 }
 
+#[test]
+fn test_free_basic_lands() {
+  let deck_text = "10 Island\r\n4 Treasure Hunt";
+  let mut deck = Deck::from_goldfish_block(String::from("test id"), String::from(deck_text));
+
+  deck.update_pricing(Vec::new(), &PricePolicy::default());
+
+  assert_eq!(deck.mainboard.first().unwrap().price, Some(0));
+  assert_eq!(deck.mainboard.get(1).unwrap().price, None);
+}
+
+#[test]
+fn test_unmatched_cards() {
+  let deck_text = "10 Island\r\n4 Treasure Hnut\r\n\r\n2 Treasure Hnut";
+  let mut deck = Deck::from_goldfish_block(String::from("test id"), String::from(deck_text));
+
+  deck.update_pricing(Vec::new(), &PricePolicy::default());
+  let unmatched: Vec<&str> = deck.unmatched_cards().iter().map(|card| card.name.as_str()).collect();
+
+  assert_eq!(unmatched, vec!["Treasure Hnut"]);
+}
+
 #[test]
 fn test_mainboard_pricing() {
-  let mut cards: Vec<Card> = Vec::new();
-  cards.push(Card { quantity: 10, name: String::from("Island"), price: Some(100) });
-  cards.push(Card { quantity: 1, name: String::from("Island"), price: None });
+  let cards: Vec<Card> = vec![
+    Card { price: Some(100), ..Card::new(10, String::from("Island")) },
+    Card::new(1, String::from("Island"))
+  ];
 
   let deck = Deck {
     mainboard: cards,
     sideboard: Vec::new(),
-    goldfish_id: String::from("test")
+    id: String::from("test"),
+    name: None,
+    source: None
   };
 
   assert_eq!(deck.mainboard_pricing(), 1000);
@@ -216,14 +568,17 @@ fn test_mainboard_pricing() {
 
 #[test]
 fn test_sideboard_pricing() {
-  let mut cards: Vec<Card> = Vec::new();
-  cards.push(Card { quantity: 10, name: String::from("Island"), price: Some(100) });
-  cards.push(Card { quantity: 1, name: String::from("Island"), price: None });
+  let cards: Vec<Card> = vec![
+    Card { price: Some(100), ..Card::new(10, String::from("Island")) },
+    Card::new(1, String::from("Island"))
+  ];
 
   let deck = Deck {
     mainboard: Vec::new(),
     sideboard: cards,
-    goldfish_id: String::from("test")
+    id: String::from("test"),
+    name: None,
+    source: None
   };
 
   assert_eq!(deck.sideboard_pricing(), 1000);
@@ -235,5 +590,163 @@ fn test_to_hash() {
   let id = "test id";
   let deck = Deck::from_goldfish_block(String::from(id), String::from(deck_text));
 
-  assert_eq!(deck.to_hash(), "D0DFF733D658");
+  assert_eq!(deck.to_versioned_hash(HashVersion::V1), "D0DFF733D658");
+  assert!(deck.to_hash().starts_with("v2-"));
+  assert_eq!(deck.to_hash().len(), 15);
+}
+
+#[test]
+fn test_hash_covers_sideboard() {
+  let deck = Deck::from_goldfish_block(String::from("a"), String::from("4 Treasure Hunt\r\n\r\n3 Negate"));
+  let other = Deck::from_goldfish_block(String::from("b"), String::from("4 Treasure Hunt\r\n\r\n3 Duress"));
+
+  assert_eq!(deck.to_versioned_hash(HashVersion::V1), other.to_versioned_hash(HashVersion::V1));
+  assert_ne!(deck.to_hash(), other.to_hash());
+}
+
+#[test]
+fn test_hash_uses_listed_names() {
+  let deck_text = "4 Æther Vial\r\n4 Lim-Dul’s Vault\r\n52 Island\r\n\r\n2 Fire/Ice";
+  let deck = Deck::from_goldfish_block(String::from("test id"), String::from(deck_text));
+
+  // Display names are normalized, but hashes match the ones issued before that
+  assert_eq!(deck.mainboard[0].name, "Aether Vial");
+  assert_eq!(deck.to_versioned_hash(HashVersion::V1), "511D2622958B");
+  assert_eq!(deck.to_hash(), "v2-64C3217A6F79");
+}
+
+#[test]
+fn test_diff() {
+  let original = Deck::from_goldfish_block(String::from("a"), String::from("4 Treasure Hunt\r\n26 Island\r\n\r\n3 Negate"));
+  let current = Deck::from_goldfish_block(String::from("b"), String::from("4 Treasure Hunt\r\n24 Island\r\n2 Swamp\r\n\r\n3 Duress"));
+
+  assert_eq!(current.diff(&original), vec![
+    "Mainboard: -2 Island",
+    "Mainboard: +2 Swamp",
+    "Sideboard: +3 Duress",
+    "Sideboard: -3 Negate"
+  ]);
+  assert!(current.diff(&current).is_empty());
+}
+
+#[test]
+fn test_sum_prices_overflow() {
+  let cards: Vec<Card> = vec![
+    Card { price: Some(2000), ..Card::new(4000000, String::from("Treasure Hunt")) },
+    Card { price: Some(50), ..Card::new(4, String::from("Zombie Infestation")) }
+  ];
+  let deck = Deck::from_cards(String::from("overflow"), cards, Vec::new());
+
+  assert_eq!(deck.mainboard_pricing(), Cents::MAX);
+}
+
+#[test]
+fn test_parse_errors() {
+  let error = Deck::parse_text_export(String::from("pasted"), "4 Treasure Hunt\n// comment\nLightning Bolt\n").unwrap_err();
+  assert_eq!(error, DreadbotError::Parse { line: Some(3), message: String::from("\"Lightning Bolt\" is not a card.") });
+
+  let error = Deck::parse_goldfish_block(String::from("10108"), String::from("<html>\r\n<body>")).unwrap_err();
+  assert_eq!(error, DreadbotError::Parse { line: Some(1), message: String::from("\"<html>\" is not a card.") });
+
+  let error = Deck::parse_goldfish_block(String::from("10108"), String::from("\r\n")).unwrap_err();
+  assert_eq!(error, DreadbotError::Parse { line: None, message: String::from("the list has no cards.") });
+
+  let deck = Deck::parse_text_export(String::from("pasted"), "Deck\n4 Treasure Hunt (EXO) 50\n\nSideboard\nSB: 3 Negate [M20]").unwrap();
+  assert_eq!(Deck::card_count(deck.mainboard()), 4);
+  assert_eq!(Deck::card_count(deck.sideboard()), 3);
+}
+
+#[test]
+fn test_legal_deck() {
+  let deck_text = "4 Treasure Hunt\r\n4 Zombie Infestation\r\n26 Island\r\n26 Swamp\r\n\r\n15 Relentless Rats";
+  let deck = Deck::from_goldfish_block(String::from("test id"), String::from(deck_text));
+
+  assert!(deck.legality_violations(&DeckRules::default()).is_empty());
+}
+
+#[test]
+fn test_legality_violations() {
+  let deck_text = "4 Treasure Hunt\r\n4 Zombie Infestation\r\n32 Island\r\n\r\n2 Treasure Hunt\r\n20 Negate";
+  let deck = Deck::from_goldfish_block(String::from("test id"), String::from(deck_text));
+
+  assert_eq!(deck.legality_violations(&DeckRules::default()), vec![
+    "Mainboard has 40 cards, at least 60 are required.",
+    "Sideboard has 22 cards, at most 15 are allowed.",
+    "20 copies of Negate, at most 4 are allowed.",
+    "6 copies of Treasure Hunt, at most 4 are allowed."
+  ]);
+}
+
+#[test]
+fn test_copies_counted_across_spellings() {
+  let deck_text = "2 Lim-Dûl's Vault\r\n3 Lim-Dul’s Vault\r\n55 Island";
+  let mut deck = Deck::from_goldfish_block(String::from("test id"), String::from(deck_text));
+
+  // Until the cards are priced, the spelling that sorts first is reported
+  assert_eq!(deck.legality_violations(&DeckRules::default()), vec![
+    "5 copies of Lim-Dul's Vault, at most 4 are allowed."
+  ]);
+
+  deck.update_pricing(vec![test_source("Lim-Dûl's Vault", Some(100), None)], &PricePolicy::default());
+  assert_eq!(deck.legality_violations(&DeckRules::default()), vec![
+    "5 copies of Lim-Dûl's Vault, at most 4 are allowed."
+  ]);
+}
+
+#[test]
+fn test_huge_quantities_saturate() {
+  let deck_text = "4 Treasure Hunt\r\n56 Island\r\n\r\n4294967295 Negate\r\n2 Negate";
+  let deck = Deck::from_goldfish_block(String::from("test id"), String::from(deck_text));
+
+  assert_eq!(deck.legality_violations(&DeckRules::default()), vec![
+    "Sideboard has 4294967295 cards, at most 15 are allowed.",
+    "4294967295 copies of Negate, at most 4 are allowed."
+  ]);
+}
+
+#[test]
+fn test_banned_and_restricted() {
+  let deck_text = "2 Fire/Ice\r\n2 Delver of Secrets\r\n56 Island\r\n\r\n1 Delver of Secrets";
+  let deck = Deck::from_goldfish_block(String::from("test id"), String::from(deck_text));
+  let rules = DeckRules {
+    banned: vec![String::from("Fire // Ice")],
+    restricted: vec![String::from("Delver of Secrets // Insectile Aberration")],
+    ..DeckRules::default()
+  };
+
+  assert_eq!(deck.legality_violations(&rules), vec![
+    "3 copies of Delver of Secrets, at most 1 are allowed.",
+    "Fire // Ice is banned."
+  ]);
+}
+
+#[test]
+fn test_base_format_legality() {
+  let deck_text = "4 Treasure Hunt\r\n4 Ponder\r\n52 Island";
+  let mut deck = Deck::from_goldfish_block(String::from("test id"), String::from(deck_text));
+  let mut entries: Vec<PricingSource> = Vec::new();
+
+  for (name, status) in &[("Treasure Hunt", "legal"), ("Ponder", "banned")] {
+    let mut entry = test_source(name, Some(10), None);
+    entry.legalities.insert(String::from("modern"), String::from(*status));
+    entries.push(entry);
+  }
+
+  deck.update_pricing(entries, &PricePolicy::default());
+  let rules = DeckRules { base_format: Some(String::from("modern")), ..DeckRules::default() };
+
+  assert_eq!(deck.legality_violations(&rules), vec!["Ponder is banned in modern."]);
+}
+
+#[test]
+fn test_matches_hash() {
+  let deck_text = "10 Island\r\n4 Treasure Hunt\r\n4 Zombie Infestation\r\n\r\n26 Island";
+  let deck = Deck::from_goldfish_block(String::from("test id"), String::from(deck_text));
+  let v2 = deck.to_hash();
+
+  assert!(deck.matches_hash("D0DFF733D658"));
+  assert!(deck.matches_hash("d0dff733d658"));
+  assert!(deck.matches_hash(&v2));
+  assert!(deck.matches_hash(&v2.to_lowercase().replace("v2-", "V2-")));
+  assert!(!deck.matches_hash("v2-D0DFF733D658"));
 }
@@ -0,0 +1,54 @@
+use std::fmt;
+
+// Everything that can stop a deck from being fetched, read or priced, worded for the player
+#[derive(Debug, PartialEq)]
+pub enum DreadbotError {
+  SourceNotFound { source: String, id: String },
+  SourcePrivate { source: String, id: String },
+  SourceUnavailable { source: String, reason: String },
+  PricingUnavailable(String),
+  // Line numbers start at 1. None when the problem is not tied to a line.
+  Parse { line: Option<usize>, message: String },
+  UnmatchedCards(Vec<String>)
+}
+
+impl fmt::Display for DreadbotError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      DreadbotError::SourceNotFound { source, id } =>
+        write!(f, "{} has no decklist with id {:?}.", source, id),
+      DreadbotError::SourcePrivate { source, id } =>
+        write!(f, "Decklist {:?} on {} is private. Make it public or unlisted and try again.", id, source),
+      DreadbotError::SourceUnavailable { source, reason } =>
+        write!(f, "{} could not be reached ({}). Please try again in a few minutes.", source, reason),
+      DreadbotError::PricingUnavailable(reason) =>
+        write!(f, "The deck was found, but Scryfall could not price it ({}). Please try again in a few minutes.", reason),
+      DreadbotError::Parse { line: Some(line), message } =>
+        write!(f, "The decklist could not be read. Line {}: {}", line, message),
+      DreadbotError::Parse { line: None, message } =>
+        write!(f, "The decklist could not be read: {}", message),
+      DreadbotError::UnmatchedCards(names) =>
+        write!(f, "Some cards could not be found: {}.", names.join(", "))
+    }
+  }
+}
+
+impl std::error::Error for DreadbotError {}
+
+impl From<serde_json::Error> for DreadbotError {
+  fn from(error: serde_json::Error) -> Self {
+    DreadbotError::Parse { line: Some(error.line()), message: String::from("the response is not a decklist this bot understands.") }
+  }
+}
+
+#[test]
+fn test_error_messages() {
+  let not_found = DreadbotError::SourceNotFound { source: String::from("MTGGoldfish"), id: String::from("10108") };
+  assert_eq!(not_found.to_string(), "MTGGoldfish has no decklist with id \"10108\".");
+
+  let parse = DreadbotError::Parse { line: Some(3), message: String::from("\"Lightning Bolt\" is not a card.") };
+  assert_eq!(parse.to_string(), "The decklist could not be read. Line 3: \"Lightning Bolt\" is not a card.");
+
+  let unmatched = DreadbotError::UnmatchedCards(vec![String::from("Lightnig Bolt"), String::from("Ponderr")]);
+  assert_eq!(unmatched.to_string(), "Some cards could not be found: Lightnig Bolt, Ponderr.");
+}
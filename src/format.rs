@@ -0,0 +1,185 @@
+extern crate serde_derive;
+use serde::{Deserialize};
+use super::card::{Cents, format_cents};
+use super::names::names_match;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+#[derive(Default)]
+pub enum BasicLandPolicy {
+  // Basic lands are never looked up and cost nothing
+  #[default]
+  Free,
+  // Basic lands are priced like every other card
+  Priced
+}
+
+
+fn default_allow_foil() -> bool { true }
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct PricePolicy {
+  // When set, a foil printing can be the cheapest printing of a card
+  #[serde(default = "default_allow_foil")]
+  pub allow_foil: bool,
+  #[serde(default)]
+  pub basic_lands: BasicLandPolicy
+}
+
+impl Default for PricePolicy {
+  fn default() -> Self {
+    PricePolicy { allow_foil: true, basic_lands: BasicLandPolicy::Free }
+  }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct DeckRules {
+  pub min_mainboard: u32,
+  pub max_sideboard: u32,
+  // Copies of a card allowed across the mainboard and sideboard combined
+  pub max_copies: u32,
+  // Cards that may not be played at any price
+  pub banned: Vec<String>,
+  // Cards limited to a single copy across both boards
+  pub restricted: Vec<String>,
+  // Scryfall format every card must also be legal in, e.g. "modern"
+  pub base_format: Option<String>
+}
+
+impl Default for DeckRules {
+  fn default() -> Self {
+    DeckRules { min_mainboard: 60, max_sideboard: 15, max_copies: 4, banned: Vec::new(), restricted: Vec::new(), base_format: None }
+  }
+}
+
+impl DeckRules {
+  pub fn is_banned(&self, name: &str) -> bool {
+    self.banned.iter().any(|banned| names_match(banned, name))
+  }
+
+  pub fn is_restricted(&self, name: &str) -> bool {
+    self.restricted.iter().any(|restricted| names_match(restricted, name))
+  }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Format {
+  pub name: String,
+  pub maindeck_limit: Cents,
+  pub sideboard_limit: Cents,
+  #[serde(default)]
+  pub price_policy: PricePolicy,
+  #[serde(default)]
+  pub rules: DeckRules
+}
+
+impl Format {
+  pub fn paper_dreadful() -> Self {
+    Format {
+      name: String::from("Paper Dreadful"),
+      maindeck_limit: 20_00,
+      sideboard_limit: 5_00,
+      price_policy: PricePolicy::default(),
+      rules: DeckRules::default()
+    }
+  }
+
+  pub fn summary(&self) -> String {
+    format!(
+      "{}: maindeck {}, sideboard {}",
+      self.name, format_cents(self.maindeck_limit), format_cents(self.sideboard_limit)
+    )
+  }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct FormatConfig {
+  pub default: String,
+  pub formats: Vec<Format>,
+  // Channel id to format name, for channels that do not use the default format
+  #[serde(default)]
+  pub channels: HashMap<String, String>
+}
+
+impl Default for FormatConfig {
+  fn default() -> Self {
+    let format = Format::paper_dreadful();
+    FormatConfig { default: format.name.clone(), formats: vec![format], channels: HashMap::new() }
+  }
+}
+
+impl FormatConfig {
+  pub fn from_json(contents: &str) -> Result<Self, Box<dyn std::error::Error>> {
+    let config: FormatConfig = serde_json::from_str(contents)?;
+
+    if config.get(&config.default).is_none() {
+      return Err(format!("Default format {:?} is not defined", config.default).into());
+    }
+
+    for (channel, name) in &config.channels {
+      if config.get(name).is_none() {
+        return Err(format!("Channel {} uses undefined format {:?}", channel, name).into());
+      }
+    }
+
+    Ok(config)
+  }
+
+  pub fn from_file(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+    FormatConfig::from_json(&fs::read_to_string(path)?)
+  }
+
+  pub fn get(&self, name: &str) -> Option<&Format> {
+    self.formats.iter().find(|format| format.name.eq_ignore_ascii_case(name))
+  }
+
+  pub fn for_channel(&self, channel_id: &str) -> &Format {
+    self.channels.get(channel_id)
+      .and_then(|name| self.get(name))
+      .or_else(|| self.get(&self.default))
+      .unwrap()
+  }
+}
+
+#[test]
+fn test_default_config() {
+  let config = FormatConfig::default();
+  let format = config.for_channel("1234");
+
+  assert_eq!(format.maindeck_limit, 20_00);
+  assert_eq!(format.sideboard_limit, 5_00);
+  assert!(format.price_policy.allow_foil);
+  assert_eq!(format.price_policy.basic_lands, BasicLandPolicy::Free);
+  assert_eq!(format.rules.min_mainboard, 60);
+  assert_eq!(format.rules.max_sideboard, 15);
+  assert_eq!(format.rules.max_copies, 4);
+}
+
+#[test]
+fn test_config_file() {
+  let config = FormatConfig::from_json(include_str!("../formats.json")).unwrap();
+
+  assert_eq!(config.for_channel("1234").name, "Paper Dreadful");
+
+  let budget = config.for_channel("600000000000000000");
+  assert_eq!(budget.name, "Pauper Budget");
+  assert_eq!(budget.maindeck_limit, 10_00);
+  assert!(!budget.price_policy.allow_foil);
+  assert_eq!(budget.price_policy.basic_lands, BasicLandPolicy::Priced);
+  assert_eq!(budget.rules.min_mainboard, 40);
+  assert_eq!(budget.rules.max_sideboard, 15);
+  assert!(budget.rules.is_banned("Fire/Ice"));
+  assert!(budget.rules.is_restricted("Delver of Secrets"));
+  assert_eq!(budget.rules.base_format, Some(String::from("pauper")));
+  assert!(!config.for_channel("1234").rules.is_banned("Fire // Ice"));
+}
+
+#[test]
+fn test_undefined_default() {
+  let config = FormatConfig::from_json(r#"{ "default": "Missing", "formats": [] }"#);
+  assert!(config.is_err());
+}
@@ -1,6 +1,38 @@
-pub fn retrieve_deck(id: &str) -> Result<String, Box<dyn std::error::Error>> {
+use super::deck::Deck;
+use super::error::DreadbotError;
+use super::source::{DeckSource, fetch_text};
+use async_trait::async_trait;
+
+pub struct Goldfish;
+
+#[async_trait]
+impl DeckSource for Goldfish {
+  fn name(&self) -> &'static str {
+    "MTGGoldfish"
+  }
+
+  fn url_pattern(&self) -> &'static str {
+    r"^https://www\.mtggoldfish\.com/deck/(\d+)"
+  }
+
+  async fn retrieve(&self, id: &str) -> Result<String, DreadbotError> {
     let uri = format!("https://www.mtggoldfish.com/deck/download/{}", id);
-    let resp: String = reqwest::get(&uri)?.text()?;
+    fetch_text(self, id, &uri).await
+  }
+
+  fn deck_url(&self, id: &str) -> Option<String> {
+    Some(format!("https://www.mtggoldfish.com/deck/{}", id))
+  }
+
+  fn parse(&self, id: &str, body: &str) -> Result<Deck, DreadbotError> {
+    Deck::parse_goldfish_block(String::from(id), String::from(body))
+  }
+}
+
+#[test]
+fn test_goldfish_fixture() {
+  let deck = Goldfish.parse("10108", include_str!("../fixtures/goldfish.txt")).unwrap();
+  let names: Vec<&str> = deck.cards().map(|c| c.name.as_str()).collect();
 
-    Ok(resp)
+  assert_eq!(names, vec!["Island", "Swamp", "Treasure Hunt", "Zombie Infestation", "Negate"]);
 }
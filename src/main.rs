@@ -1,23 +1,59 @@
 mod goldfish;
+mod moxfield;
+mod archidekt;
+mod tappedout;
+mod pasted;
+mod attachment;
 extern crate regex;
 
 mod deck;
 mod card;
+mod names;
+mod error;
 mod scryfall;
-
-use card::{Cents, format_cents};
-use goldfish::{retrieve_deck};
-use deck::Deck;
+mod source;
+mod bulk;
+mod snapshot;
+mod registry;
+mod format;
+mod budget;
+mod cache;
+mod verdict;
+
+use bulk::PriceIndex;
+use cache::PriceCache;
+use deck::{Deck, HashVersion, normalize_hash};
+use error::DreadbotError;
+use format::{Format, FormatConfig};
 use regex::Regex;
+use registry::{Registry, Submission};
+use scryfall::{PricingSource, ScryfallClient};
+use snapshot::SnapshotStore;
+use source::{DeckSource, find_source};
 use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use verdict::Verdict;
 use serenity::{
-    model::{channel::Message, gateway::Ready},
+    async_trait,
+    builder::{CreateApplicationCommand, CreateEmbed},
+    model::{
+        application::command::{Command, CommandOptionType},
+        application::interaction::{Interaction, InteractionResponseType},
+        application::interaction::application_command::{ApplicationCommandInteraction, CommandDataOptionValue},
+        channel::{Attachment, Message},
+        gateway::{GatewayIntents, Ready},
+        id::ChannelId,
+        permissions::Permissions,
+        user::User,
+    },
     prelude::*,
+    utils::Colour,
 };
 
-const MAINDECK_LIMIT: Cents = 20_00;
-const SIDEBOARD_LIMIT: Cents = 5_00;
-const DREADBOT_PREFIX: &str = r"^\$\$(.*)$";
+const DREADBOT_PREFIX: &str = r"(?s)^\$\$(.*)$";
+const DEFAULT_PRICE_TTL_HOURS: i64 = 24;
 const HELP_TEXT: &str =
 r"
 ```
@@ -29,159 +65,819 @@ $$verify <url> - Verify a decklist
 $$hash <url>   - Check the hash of a decklist
 $$info <url>   - Receive an itemized list of prices for a deck.
                  The response is lengthy so try to keep this to PMs.
+$$budget <url> - Suggest cuts and cheaper cards to get a deck under budget
+$$check <url> <hash> - Check a decklist against a previously issued hash
+$$lookup <hash> - Show the decklist that was accepted with a hash
+$$formats      - List the budget formats and the one used in this channel
+
+Organizer commands:
+$$snapshot create <name>   - Freeze the current bulk data prices
+$$snapshot list            - List stored price snapshots
+$$snapshot activate <name> - Price every deck against a snapshot
+$$snapshot deactivate      - Go back to current prices
+$$cache                    - Show how many live prices are cached
+$$cache flush              - Drop every cached price
+
+Slash commands: /verify, /info and /hash take a url or a decklist file,
+and /help shows this message. /info replies are only shown to you.
+
+Supported sites: MTGGoldfish, Moxfield, Archidekt, TappedOut
+A list can also be pasted in a code block after the command, or attached
+as a .txt, .dec (Apprentice, Arena) or .dek (MTGO) file with no url.
 ```
 ";
 
-struct Handler;
+struct Handler {
+    // Ready fires again on every gateway reconnect, but commands only need registering once
+    commands_registered: AtomicBool
+}
+
+struct PriceIndexKey;
+
+impl TypeMapKey for PriceIndexKey {
+    type Value = Arc<PriceIndex>;
+}
+
+struct ActiveSnapshotKey;
+
+impl TypeMapKey for ActiveSnapshotKey {
+    type Value = (String, Arc<PriceIndex>);
+}
+
+struct RegistryKey;
+
+impl TypeMapKey for RegistryKey {
+    type Value = Arc<Mutex<Registry>>;
+}
+
+struct ScryfallClientKey;
+
+impl TypeMapKey for ScryfallClientKey {
+    type Value = Arc<ScryfallClient>;
+}
+
+struct PriceCacheKey;
+
+impl TypeMapKey for PriceCacheKey {
+    type Value = Arc<Mutex<PriceCache>>;
+}
+
+struct FormatConfigKey;
+
+impl TypeMapKey for FormatConfigKey {
+    type Value = Arc<FormatConfig>;
+}
+
+// Clones a value out of the shared data, so the lock is not held while a command awaits
+async fn shared<K: TypeMapKey>(ctx: &Context) -> Option<K::Value> where K::Value: Clone {
+    ctx.data.read().await.get::<K>().cloned()
+}
+
+async fn channel_format(ctx: &Context, channel_id: ChannelId) -> Format {
+    match ctx.data.read().await.get::<FormatConfigKey>() {
+        Some(config) => config.for_channel(&channel_id.0.to_string()).clone(),
+        None => Format::paper_dreadful()
+    }
+}
+
+fn snapshot_store() -> SnapshotStore {
+    let directory = env::var("DREADBOT_SNAPSHOT_DIR").unwrap_or_else(|_| String::from("snapshots"));
+    SnapshotStore::new(PathBuf::from(directory))
+}
+
+fn is_organizer(msg: &Message) -> bool {
+    let organizers = env::var("DREADBOT_ORGANIZERS").unwrap_or_default();
+    let author_id = msg.author.id.0.to_string();
+
+    organizers.split(',').any(|id| id.trim() == author_id)
+}
+
+async fn active_index(ctx: &Context) -> Option<Arc<PriceIndex>> {
+    let data = ctx.data.read().await;
+    match data.get::<ActiveSnapshotKey>() {
+        Some((_, snapshot_index)) => Some(snapshot_index.clone()),
+        None => data.get::<PriceIndexKey>().cloned()
+    }
+}
+
+async fn scryfall_client(ctx: &Context) -> Result<Arc<ScryfallClient>, DreadbotError> {
+    match shared::<ScryfallClientKey>(ctx).await {
+        Some(client) => Ok(client),
+        None => ScryfallClient::new().map(Arc::new)
+    }
+}
 
-fn fetch_deck(id: &str) -> Option<Deck> {
-    let response = match retrieve_deck(id) {
-        Ok(resp) => resp,
-        _ => return None
+// Cached entries first, then a live Scryfall query for the cards that are missing or stale
+async fn live_pricing(ctx: &Context, deck: &Deck, format: &Format) -> Result<Vec<PricingSource>, DreadbotError> {
+    let client = scryfall_client(ctx).await?;
+    let cache = match shared::<PriceCacheKey>(ctx).await {
+        Some(cache) => cache,
+        None => return client.request_pricing(deck, &format.price_policy).await
     };
 
-    let mut deck = Deck::from_goldfish_block(String::from(id), response);
+    let now = cache::now();
+    let lookup = cache.lock().unwrap().lookup(deck, now);
+    let (mut entries, missing) = match lookup {
+        Ok(found) => found,
+        Err(why) => {
+            println!("Error reading the price cache: {:?}", why);
+            (Vec::new(), deck.cards().cloned().collect())
+        }
+    };
+
+    if missing.is_empty() { return Ok(entries) }
+
+    let missing_deck = Deck::from_cards(String::from("missing"), missing, Vec::new());
+    let fetched = client.request_pricing(&missing_deck, &format.price_policy).await?;
+    if let Err(why) = cache.lock().unwrap().store(&fetched, now) {
+        println!("Error writing the price cache: {:?}", why);
+    }
 
-    let scryfall_resp = match scryfall::request_pricing(&deck) {
-        Ok(resp) => resp,
-        _ => return None
+    entries.extend(fetched);
+    Ok(entries)
+}
+
+async fn price_deck(ctx: &Context, mut deck: Deck, format: &Format) -> Result<Deck, DreadbotError> {
+    // An active snapshot wins, then the offline bulk data index, then cached or live Scryfall prices
+    let index = active_index(ctx).await;
+
+    let scryfall_resp = match index {
+        Some(index) => index.pricing_for(&deck),
+        None => live_pricing(ctx, &deck, format).await?
     };
 
-    deck.update_pricing(scryfall_resp);
-    Some(deck)
+    deck.update_pricing(scryfall_resp, &format.price_policy);
+    Ok(deck)
+}
+
+async fn fetch_deck(ctx: &Context, source: &dyn DeckSource, id: &str, format: &Format) -> Result<Deck, DreadbotError> {
+    let response = source.retrieve(id).await?;
+    let mut deck = source.parse(id, &response)?;
+    if let Some(url) = source.deck_url(id) {
+        deck.set_source(url);
+    }
+
+    price_deck(ctx, deck, format).await
 }
 
-fn respond(ctx: &Context, msg: &Message, response: &str) -> bool {
-    if let Err(why) = msg.channel_id.say(&ctx.http, response) {
+async fn fetch_attachment_deck(ctx: &Context, attachment: &Attachment, format: &Format) -> Result<Deck, DreadbotError> {
+    let bytes = attachment.download().await
+        .map_err(|why| DreadbotError::SourceUnavailable { source: String::from("Discord"), reason: why.to_string() })?;
+
+    let body = String::from_utf8_lossy(&bytes);
+    let mut deck = attachment::parse_attachment(&attachment.filename, &body)?;
+    deck.set_source(attachment.url.clone());
+
+    price_deck(ctx, deck, format).await
+}
+
+async fn respond(ctx: &Context, msg: &Message, response: &str) -> bool {
+    if let Err(why) = msg.channel_id.say(&ctx.http, response).await {
         println!("Error sending response: {:?}", why);
     }
 
     true
 }
 
-fn respond_to_deck(ctx: &Context, msg: &Message, deck: &Deck) -> bool {
-    let maindeck_price = deck.mainboard_pricing();
-    let sideboard_price = deck.sideboard_pricing();
-    let formatted_maindeck = format_cents(maindeck_price);
-    let formatted_sideboard= format_cents(sideboard_price);
-
-    let maindeck_over = deck.mainboard_pricing() <= MAINDECK_LIMIT;
-    let sideboard_over = deck.sideboard_pricing() <= SIDEBOARD_LIMIT;
-    let response = match (maindeck_over, sideboard_over) {
-        (true, true) =>
-            format!(
-                ":white_check_mark: Deck accepted!\nDeck hash: {}\nMaindeck price: {}\nSideboard price: {}",
-                deck.to_hash(), formatted_maindeck, formatted_sideboard
-            ),
-        (true, false) =>
-            format!(
-                ":x: Deck error! Sideboard overpriced.\nMaindeck price: {}\nSideboard price: {}",
-                formatted_maindeck, formatted_sideboard
-            ),
-        (false, true) =>
-            format!(
-                ":x: Deck error! Maindeck overpriced.\nMaindeck price: {}\nSideboard price: {}",
-                formatted_maindeck, formatted_sideboard
-            ),
-        _ =>
-            format!(
-                ":x: Deck error! Maindeck and sideboard overpriced.\nMaindeck price: {}\nSideboard price: {}",
-                formatted_maindeck, formatted_sideboard
-            )
-    };
-
-    respond(ctx, &msg, &response)
-}
-
-fn retrieve_or_error(ctx: &Context, msg: &Message, regex: Regex, parsed_message: &str) -> Option<Deck> {
+const SUGGESTION_COUNT: usize = 3;
+
+// Looks for the names a player probably meant, locally when an index is loaded
+async fn name_suggestions(ctx: &Context, name: &str) -> Vec<String> {
+    match active_index(ctx).await {
+        Some(index) => index.suggestions(name, SUGGESTION_COUNT),
+        None => match scryfall_client(ctx).await {
+            Ok(client) => client.request_suggestions(name).await
+                .map(|names| names.into_iter().take(SUGGESTION_COUNT).collect())
+                .unwrap_or_default(),
+            Err(_) => Vec::new()
+        }
+    }
+}
+
+async fn unmatched_report(ctx: &Context, deck: &Deck) -> Vec<String> {
+    let mut report: Vec<String> = Vec::new();
+
+    for card in deck.unmatched_cards() {
+        let suggestions = name_suggestions(ctx, &card.name).await;
+        if suggestions.is_empty() {
+            report.push(format!("{} could not be priced.", card.name));
+        } else {
+            report.push(format!("{} could not be priced. Did you mean {}?", card.name, suggestions.join(", ")));
+        }
+    }
+
+    report
+}
+
+async fn record_submission(ctx: &Context, author: &User, submitted_at: String, deck: &Deck) {
+    let registry = match shared::<RegistryKey>(ctx).await {
+        Some(registry) => registry,
+        None => return
+    };
+
+    let submission = Submission::from_deck(deck, author.id.0.to_string(), author.tag(), submitted_at);
+
+    let recorded = registry.lock().unwrap().record(&submission);
+    if let Err(why) = recorded {
+        println!("Error recording submission: {:?}", why);
+    }
+}
+
+async fn deck_verdict(ctx: &Context, deck: &Deck, format: &Format) -> Verdict {
+    Verdict::new(deck, format, unmatched_report(ctx, deck).await)
+}
+
+fn verdict_embed<'a>(embed: &'a mut CreateEmbed, verdict: &Verdict) -> &'a mut CreateEmbed {
+    let mut description = String::from(verdict.headline());
+    let problems = verdict.problems();
+    if !problems.is_empty() {
+        description += &format!("\n\n{}", problems.join("\n"));
+    }
+
+    embed
+        .title(&verdict.deck_name)
+        .colour(if verdict.is_accepted() { Colour::DARK_GREEN } else { Colour::RED })
+        .description(description)
+        .field("Maindeck", verdict.board_total(verdict.maindeck_price, verdict.maindeck_limit), true)
+        .field("Sideboard", verdict.board_total(verdict.sideboard_price, verdict.sideboard_limit), true)
+        .field("Remaining budget", verdict.remaining_budget(), false);
+
+    // Pasted decks and attachments have no page to link to
+    if let Some(source) = verdict.source.as_ref().filter(|source| source.starts_with("http")) {
+        embed.url(source);
+    }
+
+    if verdict.is_accepted() {
+        embed.field("Hash", &verdict.hash, false);
+    }
+
+    embed
+}
+
+// Without Embed Links in a server channel Discord drops the embed, so those channels get the text reply.
+// Direct messages and channels missing from the cache are assumed to allow embeds.
+fn embeds_allowed(ctx: &Context, channel_id: ChannelId) -> bool {
+    match ctx.cache.guild_channel(channel_id) {
+        Some(channel) => channel.permissions_for_user(&ctx.cache, ctx.cache.current_user_id())
+            .map(|permissions| permissions.contains(Permissions::EMBED_LINKS))
+            .unwrap_or(true),
+        None => true
+    }
+}
+
+async fn respond_verdict(ctx: &Context, msg: &Message, verdict: &Verdict) -> bool {
+    if !embeds_allowed(ctx, msg.channel_id) {
+        return respond(ctx, msg, &verdict.to_text()).await;
+    }
+
+    if let Err(why) = msg.channel_id.send_message(&ctx.http, |m| m.embed(|e| verdict_embed(e, verdict))).await {
+        println!("Error sending response: {:?}", why);
+    }
+
+    true
+}
+
+// Once the player has been told why their deck could not be read, the message is handled and
+// must not fall through to the other commands or the help text
+enum Retrieved {
+    Deck(Deck),
+    Replied,
+    NotHandled
+}
+
+async fn retrieve_or_error(ctx: &Context, msg: &Message, command: &str, parsed_message: &str) -> Retrieved {
+    let regex = Regex::new(&format!(r"(?s)^{}(?:\s+(.*))?$", command)).unwrap();
+    let captures = match regex.captures(parsed_message) {
+        Some(c) => c,
+        None => return Retrieved::NotHandled
+    };
+
+    let argument = captures.get(1).map(|c| c.as_str().trim()).unwrap_or("");
+    retrieve_argument_or_error(ctx, msg, argument).await
+}
+
+async fn retrieve_argument_or_error(ctx: &Context, msg: &Message, argument: &str) -> Retrieved {
+    let format = channel_format(ctx, msg.channel_id).await;
+
+    // Without an argument, fall back to a decklist file attached to the message
+    let deck = if argument.is_empty() {
+        match msg.attachments.first() {
+            Some(attachment) => fetch_attachment_deck(ctx, attachment, &format).await,
+            None => return Retrieved::NotHandled
+        }
+    } else {
+        match find_source(argument) {
+            Some((source, id)) => fetch_deck(ctx, source, &id, &format).await,
+            None => return Retrieved::NotHandled
+        }
+    };
+
+    match deck {
+        Ok(deck) => Retrieved::Deck(deck),
+        Err(why) => {
+            respond_error(ctx, msg, &why).await;
+            Retrieved::Replied
+        }
+    }
+}
+
+// The details go to the log for organizers, the player gets the readable version
+async fn respond_error(ctx: &Context, msg: &Message, why: &DreadbotError) -> bool {
+    println!("Error handling {:?} from {}: {:?}", msg.content, msg.author.tag(), why);
+    respond(ctx, msg, &why.to_string()).await
+}
+
+async fn dreadbot_help(ctx: &Context, msg: &Message) -> bool {
+    respond(ctx, msg, HELP_TEXT).await
+}
+
+async fn dreadbot_verify(ctx: &Context, msg: &Message, parsed_message: &str) -> bool {
+    let deck = match retrieve_or_error(ctx, msg, "verify", parsed_message).await {
+        Retrieved::Deck(deck) => deck,
+        Retrieved::Replied => return true,
+        Retrieved::NotHandled => return false
+    };
+
+    let format = channel_format(ctx, msg.channel_id).await;
+    let verdict = deck_verdict(ctx, &deck, &format).await;
+    if verdict.is_accepted() {
+        record_submission(ctx, &msg.author, msg.timestamp.to_rfc3339(), &deck).await;
+    }
+
+    respond_verdict(ctx, msg, &verdict).await
+}
+
+async fn dreadbot_info(ctx: &Context, msg: &Message, parsed_message: &str) -> bool {
+    let deck = match retrieve_or_error(ctx, msg, "info", parsed_message).await {
+        Retrieved::Deck(deck) => deck,
+        Retrieved::Replied => return true,
+        Retrieved::NotHandled => return false
+    };
+
+    respond(ctx, msg, &deck.info_string()).await
+}
+
+async fn dreadbot_budget(ctx: &Context, msg: &Message, parsed_message: &str) -> bool {
+    let deck = match retrieve_or_error(ctx, msg, "budget", parsed_message).await {
+        Retrieved::Deck(deck) => deck,
+        Retrieved::Replied => return true,
+        Retrieved::NotHandled => return false
+    };
+
+    let format = channel_format(ctx, msg.channel_id).await;
+    let alternatives = price_deck(ctx, budget::alternatives_for(&deck), &format).await
+        .unwrap_or_else(|_| budget::alternatives_for(&deck));
+
+    respond(ctx, msg, &budget::budget_report(&deck, &format, &alternatives)).await
+}
+
+async fn dreadbot_hash(ctx: &Context, msg: &Message, parsed_message: &str) -> bool {
+    let deck = match retrieve_or_error(ctx, msg, "hash", parsed_message).await {
+        Retrieved::Deck(deck) => deck,
+        Retrieved::Replied => return true,
+        Retrieved::NotHandled => return false
+    };
+
+    if let Err(why) = deck.require_matched() {
+        return respond_error(ctx, msg, &why).await;
+    }
+
+    respond(ctx, msg, &format!("Deck hash: {}", &deck.to_hash())).await
+}
+
+async fn dreadbot_check(ctx: &Context, msg: &Message, parsed_message: &str) -> bool {
+    let regex = Regex::new(r"(?s)^check(?:\s+(.*?))?\s+(\S+)\s*$").unwrap();
     let captures = match regex.captures(parsed_message) {
         Some(c) => c,
-        None => return None
+        None => return false
     };
 
-    let id = match captures.get(1) {
-        Some(c) => c.as_str(),
-        None => return None
+    let argument = captures.get(1).map(|c| c.as_str().trim()).unwrap_or("");
+    let hash = normalize_hash(&captures[2]);
+
+    let deck = match retrieve_argument_or_error(ctx, msg, argument).await {
+        Retrieved::Deck(deck) => deck,
+        Retrieved::Replied => return true,
+        Retrieved::NotHandled => return false
     };
 
-    let deck = fetch_deck(id);
-    if deck.is_none() {
-        let response = format!("Decklist with id {:?} is not accessible or private.", id);
-        respond(ctx, &msg, &response);
+    if deck.matches_hash(&hash) {
+        return respond(ctx, msg, &format!(":white_check_mark: Hash matches! Deck hash: {}", hash)).await;
+    }
+
+    let mut response = format!(
+        ":x: Hash mismatch! Expected {} but the deck hashes to {}.",
+        hash, deck.to_versioned_hash(HashVersion::of(&hash))
+    );
+
+    let registry = shared::<RegistryKey>(ctx).await;
+    let submission = registry.and_then(|registry| registry.lock().unwrap().lookup(&hash).ok().and_then(|s| s));
+    if let Some(submission) = submission {
+        response += &format!(
+            "\nChanges since it was accepted for {} at {}:\n```\n{}\n```",
+            submission.user_name, submission.submitted_at, deck.diff(&submission.to_deck()).join("\n")
+        );
     }
 
-    deck
+    respond(ctx, msg, &response).await
 }
 
-fn dreadbot_help(ctx: &Context, msg: &Message) -> bool {
-    respond(ctx, &msg, HELP_TEXT)
+async fn dreadbot_formats(ctx: &Context, msg: &Message, parsed_message: &str) -> bool {
+    if parsed_message.trim() != "formats" { return false }
+
+    let config = shared::<FormatConfigKey>(ctx).await
+        .unwrap_or_else(|| Arc::new(FormatConfig::default()));
+
+    let mut response = String::from("```\n");
+    for format in &config.formats {
+        response += &format.summary();
+        response += "\n";
+    }
+
+    response += &format!("```\nThis channel uses {}.", channel_format(ctx, msg.channel_id).await.name);
+    respond(ctx, msg, &response).await
 }
 
-fn dreadbot_verify(ctx: &Context, msg: &Message, parsed_message: &str) -> bool {
-    let regex =
-        Regex::new(r"^verify https://www\.mtggoldfish\.com/deck/(\d*).*$")
-            .unwrap();
+async fn dreadbot_lookup(ctx: &Context, msg: &Message, parsed_message: &str) -> bool {
+    let regex = Regex::new(r"^lookup (\S+)\s*$").unwrap();
+    let hash = match regex.captures(parsed_message) {
+        Some(captures) => normalize_hash(&captures[1]),
+        None => return false
+    };
+
+    let registry = match shared::<RegistryKey>(ctx).await {
+        Some(registry) => registry,
+        None => return respond(ctx, msg, "The submission registry is not available.").await
+    };
+
+    let submission = registry.lock().unwrap().lookup(&hash);
+    let response = match submission {
+        Ok(Some(submission)) => format!(
+            "Deck {} was accepted for {} at {}.\nSource: {}\n{}",
+            submission.hash, submission.user_name, submission.submitted_at, submission.source,
+            submission.to_deck().info_string()
+        ),
+        Ok(None) => format!("No accepted deck has the hash {}.", hash),
+        Err(why) => format!("Could not look up {}: {}", hash, why)
+    };
+
+    respond(ctx, msg, &response).await
+}
 
-    if let Some(deck) = retrieve_or_error(&ctx, &msg, regex, parsed_message) {
-        return respond_to_deck(ctx, &msg, &deck);
+async fn dreadbot_snapshot(ctx: &Context, msg: &Message, parsed_message: &str) -> bool {
+    let regex = Regex::new(r"^snapshot (create|list|activate|deactivate)(?: (\S+))?\s*$").unwrap();
+    let captures = match regex.captures(parsed_message) {
+        Some(c) => c,
+        None => return false
+    };
+
+    if !is_organizer(msg) {
+        return respond(ctx, msg, "Only organizers can manage price snapshots.").await;
+    }
+
+    let store = snapshot_store();
+    let name = captures.get(2).map(|c| c.as_str()).unwrap_or("");
+    let needs_name = matches!(&captures[1], "create" | "activate");
+    if needs_name && !SnapshotStore::is_valid_name(name) {
+        return respond(ctx, msg, "Snapshot names may only contain letters, numbers, - and _.").await;
     }
 
-    false
+    let response = match &captures[1] {
+        "create" => {
+            let index = match shared::<PriceIndexKey>(ctx).await {
+                Some(index) => index,
+                None => return respond(ctx, msg, "Snapshots are taken from bulk data. Start the bot with SCRYFALL_BULK_PATH set.").await
+            };
+
+            // Dated by the bulk data it freezes, not by when the command was sent
+            let date = index.updated_at().unwrap_or("unknown");
+            match store.create(name, date, index.to_pricing()) {
+                Ok(snapshot) => format!("Created snapshot {} dated {} with {} prices.", snapshot.name, snapshot.date, snapshot.prices.len()),
+                Err(why) => format!("Could not create snapshot {}: {}", name, why)
+            }
+        },
+        "list" => {
+            let active = ctx.data.read().await.get::<ActiveSnapshotKey>().map(|(name, _)| name.clone());
+            match store.list() {
+                Ok(ref snapshots) if snapshots.is_empty() => String::from("No snapshots have been created."),
+                Ok(snapshots) => {
+                    let mut listing = String::from("```\n");
+                    for (snapshot_name, date) in snapshots {
+                        let marker = if Some(&snapshot_name) == active.as_ref() { " (active)" } else { "" };
+                        listing += &format!("{} {}{}\n", date, snapshot_name, marker);
+                    }
+                    listing + "```"
+                },
+                Err(why) => format!("Could not list snapshots: {}", why)
+            }
+        },
+        "activate" => match store.activate(name) {
+            Ok(snapshot) => {
+                let index = Arc::new(snapshot.to_index());
+                ctx.data.write().await.insert::<ActiveSnapshotKey>((snapshot.name.clone(), index));
+                format!("Decks are now priced against snapshot {} dated {}.", snapshot.name, snapshot.date)
+            },
+            Err(why) => format!("Could not activate snapshot {}: {}", name, why)
+        },
+        _ => match store.deactivate() {
+            Ok(()) => {
+                ctx.data.write().await.remove::<ActiveSnapshotKey>();
+                String::from("Decks are now priced against current prices.")
+            },
+            Err(why) => format!("Could not deactivate snapshot: {}", why)
+        }
+    };
+
+    respond(ctx, msg, &response).await
 }
 
-fn dreadbot_info(ctx: &Context, msg: &Message, parsed_message: &str) -> bool {
-    let regex =
-        Regex::new(r"^info https://www\.mtggoldfish\.com/deck/(\d*).*$")
-            .unwrap();
+async fn dreadbot_cache(ctx: &Context, msg: &Message, parsed_message: &str) -> bool {
+    let regex = Regex::new(r"^cache(?: (flush))?\s*$").unwrap();
+    let captures = match regex.captures(parsed_message) {
+        Some(c) => c,
+        None => return false
+    };
 
-    if let Some(deck) = retrieve_or_error(&ctx, &msg, regex, parsed_message) {
-        return respond(ctx, &msg, &deck.info_string());
+    if !is_organizer(msg) {
+        return respond(ctx, msg, "Only organizers can manage the price cache.").await;
     }
 
-    false
+    let cache = match shared::<PriceCacheKey>(ctx).await {
+        Some(cache) => cache,
+        None => return respond(ctx, msg, "The price cache is not enabled.").await
+    };
+
+    let response = {
+        let cache = cache.lock().unwrap();
+        match captures.get(1) {
+            Some(_) => match cache.flush() {
+                Ok(removed) => format!("Flushed {} cached prices.", removed),
+                Err(why) => format!("Could not flush the price cache: {}", why)
+            },
+            None => match cache.stats(cache::now()) {
+                Ok(stats) => format!(
+                    "{} card names are cached, {} of them older than {} hours and due for a refresh.",
+                    stats.names, stats.stale, cache.ttl() / 3600
+                ),
+                Err(why) => format!("Could not read the price cache: {}", why)
+            }
+        }
+    };
+
+    respond(ctx, msg, &response).await
+}
+
+fn deck_command<'a>(command: &'a mut CreateApplicationCommand, name: &str, description: &str) -> &'a mut CreateApplicationCommand {
+    command
+        .name(name)
+        .description(description)
+        .create_option(|option| option
+            .name("url")
+            .description("Link to the deck on MTGGoldfish, Moxfield, Archidekt or TappedOut")
+            .kind(CommandOptionType::String)
+            .required(false))
+        .create_option(|option| option
+            .name("decklist")
+            .description("A .txt, .dec (Apprentice, Arena) or .dek (MTGO) decklist file")
+            .kind(CommandOptionType::Attachment)
+            .required(false))
+}
+
+async fn register_slash_commands(ctx: &Context) {
+    let registered = Command::set_global_application_commands(&ctx.http, |commands| commands
+        .create_application_command(|command| deck_command(command, "verify", "Verify a decklist"))
+        .create_application_command(|command| deck_command(command, "info", "Itemized prices for a decklist, only shown to you"))
+        .create_application_command(|command| deck_command(command, "hash", "Check the hash of a decklist"))
+        .create_application_command(|command| command.name("help").description("How to use Dreadbot"))
+    ).await;
+
+    if let Err(why) = registered {
+        println!("Error registering slash commands: {:?}", why);
+    }
 }
 
-fn dreadbot_hash(ctx: &Context, msg: &Message, parsed_message: &str) -> bool {
-    let regex =
-        Regex::new(r"^hash https://www\.mtggoldfish\.com/deck/(\d*).*$")
-            .unwrap();
+async fn slash_deck(ctx: &Context, command: &ApplicationCommandInteraction, format: &Format) -> Result<Deck, DreadbotError> {
+    let mut url: Option<&str> = None;
+    let mut attachment: Option<&Attachment> = None;
 
-    if let Some(deck) = retrieve_or_error(&ctx, &msg, regex, parsed_message) {
-        return respond(ctx, &msg, &format!("Deck hash: {}", &deck.to_hash()));
+    for option in &command.data.options {
+        match &option.resolved {
+            Some(CommandDataOptionValue::String(value)) => url = Some(value.as_str()),
+            Some(CommandDataOptionValue::Attachment(value)) => attachment = Some(value),
+            _ => ()
+        }
     }
 
-    false
+    match (url.and_then(find_source), attachment) {
+        (Some((source, id)), _) => fetch_deck(ctx, source, &id, format).await,
+        (None, Some(attachment)) => fetch_attachment_deck(ctx, attachment, format).await,
+        (None, None) => Err(DreadbotError::Parse {
+            line: None,
+            message: String::from("give a link to a deck on a supported site or attach a decklist file.")
+        })
+    }
 }
 
+enum SlashReply {
+    Text(String),
+    Verdict(Verdict)
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum DeckCommand {
+    Verify,
+    Info,
+    Hash
+}
+
+impl DeckCommand {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "verify" => Some(DeckCommand::Verify),
+            "info" => Some(DeckCommand::Info),
+            "hash" => Some(DeckCommand::Hash),
+            _ => None
+        }
+    }
+}
+
+async fn slash_reply(ctx: &Context, command: &ApplicationCommandInteraction, deck_command: DeckCommand) -> Result<SlashReply, DreadbotError> {
+    let format = channel_format(ctx, command.channel_id).await;
+    let deck = slash_deck(ctx, command, &format).await?;
+
+    match deck_command {
+        DeckCommand::Verify => {
+            let verdict = deck_verdict(ctx, &deck, &format).await;
+            if verdict.is_accepted() {
+                record_submission(ctx, &command.user, command.id.created_at().to_rfc3339(), &deck).await;
+            }
+
+            Ok(SlashReply::Verdict(verdict))
+        },
+        DeckCommand::Info => Ok(SlashReply::Text(deck.info_string())),
+        DeckCommand::Hash => {
+            deck.require_matched()?;
+            Ok(SlashReply::Text(format!("Deck hash: {}", deck.to_hash())))
+        }
+    }
+}
+
+// Answers straight away, without deferring, and only to the player who asked
+async fn slash_ephemeral(ctx: &Context, command: &ApplicationCommandInteraction, content: &str) {
+    let replied = command.create_interaction_response(&ctx.http, |response| response
+        .kind(InteractionResponseType::ChannelMessageWithSource)
+        .interaction_response_data(|data| data.content(content).ephemeral(true))
+    ).await;
+
+    if let Err(why) = replied {
+        println!("Error sending response: {:?}", why);
+    }
+}
+
+// Pricing can take longer than the three seconds Discord waits for an answer, so deck commands
+// are deferred first. Itemized prices and errors are only shown to the player who asked.
+async fn slash_command(ctx: &Context, command: &ApplicationCommandInteraction) {
+    if command.data.name == "help" {
+        return slash_ephemeral(ctx, command, HELP_TEXT).await;
+    }
+
+    // Commands left over from an older registration never reach a deck source
+    let deck_command = match DeckCommand::from_name(&command.data.name) {
+        Some(deck_command) => deck_command,
+        None => {
+            println!("Unknown slash command /{} from {}", command.data.name, command.user.tag());
+            return slash_ephemeral(ctx, command, "Dreadbot does not know that command. Try /help.").await;
+        }
+    };
+
+    let ephemeral = deck_command == DeckCommand::Info;
+    let deferred = command.create_interaction_response(&ctx.http, |response| response
+        .kind(InteractionResponseType::DeferredChannelMessageWithSource)
+        .interaction_response_data(|data| data.ephemeral(ephemeral))
+    ).await;
+
+    if let Err(why) = deferred {
+        println!("Error deferring response: {:?}", why);
+        return;
+    }
+
+    let replied = match slash_reply(ctx, command, deck_command).await {
+        Ok(SlashReply::Verdict(verdict)) if embeds_allowed(ctx, command.channel_id) =>
+            command.edit_original_interaction_response(&ctx.http, |response| response.embed(|e| verdict_embed(e, &verdict)))
+                .await
+                .map(|_| ()),
+        Ok(SlashReply::Verdict(verdict)) =>
+            command.edit_original_interaction_response(&ctx.http, |response| response.content(verdict.to_text()))
+                .await
+                .map(|_| ()),
+        Ok(SlashReply::Text(reply)) => command.edit_original_interaction_response(&ctx.http, |response| response.content(reply))
+            .await
+            .map(|_| ()),
+        Err(why) => {
+            println!("Error handling /{} from {}: {:?}", command.data.name, command.user.tag(), why);
+            if !ephemeral {
+                let _ = command.delete_original_interaction_response(&ctx.http).await;
+            }
+
+            command.create_followup_message(&ctx.http, |message| message.content(why.to_string()).ephemeral(true))
+                .await
+                .map(|_| ())
+        }
+    };
+
+    if let Err(why) = replied {
+        println!("Error sending response: {:?}", why);
+    }
+}
+
+// Serenity runs every event in its own task, so a slow deck only holds up its own reply
+#[async_trait]
 impl EventHandler for Handler {
-    fn message(&self, ctx: Context, msg: Message) {
+    async fn message(&self, ctx: Context, msg: Message) {
         let regex = Regex::new(DREADBOT_PREFIX).unwrap();
 
         if let Some(captures) = regex.captures(&msg.content) {
             if let Some(remaining_message) = captures.get(1) {
-                if dreadbot_verify(&ctx, &msg, remaining_message.as_str()) { return }
-                if dreadbot_info(&ctx, &msg, remaining_message.as_str()) { return }
-                if dreadbot_hash(&ctx, &msg, remaining_message.as_str()) { return }
+                if dreadbot_verify(&ctx, &msg, remaining_message.as_str()).await { return }
+                if dreadbot_info(&ctx, &msg, remaining_message.as_str()).await { return }
+                if dreadbot_hash(&ctx, &msg, remaining_message.as_str()).await { return }
+                if dreadbot_budget(&ctx, &msg, remaining_message.as_str()).await { return }
+                if dreadbot_check(&ctx, &msg, remaining_message.as_str()).await { return }
+                if dreadbot_lookup(&ctx, &msg, remaining_message.as_str()).await { return }
+                if dreadbot_formats(&ctx, &msg, remaining_message.as_str()).await { return }
+                if dreadbot_snapshot(&ctx, &msg, remaining_message.as_str()).await { return }
+                if dreadbot_cache(&ctx, &msg, remaining_message.as_str()).await { return }
 
                 // Fallback to the help message
-                dreadbot_help(&ctx, &msg);
+                dreadbot_help(&ctx, &msg).await;
             };
         }
     }
 
-    fn ready(&self, _: Context, ready: Ready) {
+    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        if let Interaction::ApplicationCommand(command) = interaction {
+            slash_command(&ctx, &command).await;
+        }
+    }
+
+    async fn ready(&self, ctx: Context, ready: Ready) {
         println!("{} is connected!", ready.user.name);
+        if !self.commands_registered.swap(true, Ordering::SeqCst) {
+            register_slash_commands(&ctx).await;
+        }
     }
 }
 
-fn main() {
+#[tokio::main]
+async fn main() {
     let token = env::var("DISCORD_TOKEN")
         .expect("Expected a token in the environment");
 
-    let mut client = Client::new(&token, Handler)
+    let intents = GatewayIntents::GUILD_MESSAGES | GatewayIntents::DIRECT_MESSAGES | GatewayIntents::MESSAGE_CONTENT;
+    let mut client = Client::builder(&token, intents)
+        .event_handler(Handler { commands_registered: AtomicBool::new(false) })
+        .await
         .expect("Err creating client");
 
-    if let Err(why) = client.start() {
+    // Pricing from a local default_cards bulk file avoids querying Scryfall for every deck
+    if let Ok(bulk_path) = env::var("SCRYFALL_BULK_PATH") {
+        let index = PriceIndex::from_file(Path::new(&bulk_path))
+            .expect("Err loading Scryfall bulk data");
+
+        client.data.write().await.insert::<PriceIndexKey>(Arc::new(index));
+    }
+
+    // Budget formats come from a config file so limits can change without a rebuild
+    let format_config = match env::var("DREADBOT_FORMATS") {
+        Ok(formats_path) => FormatConfig::from_file(Path::new(&formats_path))
+            .expect("Err loading the format config"),
+        Err(_) => FormatConfig::default()
+    };
+    client.data.write().await.insert::<FormatConfigKey>(Arc::new(format_config));
+
+    let database_path = env::var("DREADBOT_DATABASE").unwrap_or_else(|_| String::from("dreadbot.sqlite"));
+    let registry = Registry::open(Path::new(&database_path))
+        .expect("Err opening the submission registry");
+    client.data.write().await.insert::<RegistryKey>(Arc::new(Mutex::new(registry)));
+
+    // Every Scryfall request goes through one client so they stay spaced out
+    let http = ScryfallClient::new().expect("Err creating the Scryfall client");
+    client.data.write().await.insert::<ScryfallClientKey>(Arc::new(http));
+
+    // Live Scryfall prices are reused for a few hours so repeat checks of a deck stay off the API
+    let ttl_hours = env::var("DREADBOT_PRICE_TTL")
+        .map(|hours| hours.parse::<i64>().expect("Err parsing DREADBOT_PRICE_TTL as hours"))
+        .unwrap_or(DEFAULT_PRICE_TTL_HOURS);
+    let price_cache = PriceCache::open(Path::new(&database_path), ttl_hours * 3600)
+        .expect("Err opening the price cache");
+    client.data.write().await.insert::<PriceCacheKey>(Arc::new(Mutex::new(price_cache)));
+
+    if let Some(snapshot) = snapshot_store().active().expect("Err loading the active snapshot") {
+        let index = Arc::new(snapshot.to_index());
+        client.data.write().await.insert::<ActiveSnapshotKey>((snapshot.name, index));
+    }
+
+    if let Err(why) = client.start().await {
         println!("Client error: {:?}", why);
     }
 }
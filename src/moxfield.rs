@@ -0,0 +1,89 @@
+extern crate serde_derive;
+use async_trait::async_trait;
+use serde::{Deserialize};
+use std::collections::HashMap;
+use super::card::Card;
+use super::deck::Deck;
+use super::error::DreadbotError;
+use super::source::{DeckSource, fetch_text};
+
+pub struct Moxfield;
+
+#[derive(Deserialize, Debug)]
+pub struct MoxfieldDeck {
+  pub name: Option<String>,
+  #[serde(default)]
+  pub mainboard: HashMap<String, MoxfieldEntry>,
+  #[serde(default)]
+  pub sideboard: HashMap<String, MoxfieldEntry>,
+  #[serde(default)]
+  pub commanders: HashMap<String, MoxfieldEntry>,
+  #[serde(default)]
+  pub companions: HashMap<String, MoxfieldEntry>
+}
+
+#[derive(Deserialize, Debug)]
+pub struct MoxfieldEntry {
+  pub quantity: u32,
+  pub card: MoxfieldCard
+}
+
+#[derive(Deserialize, Debug)]
+pub struct MoxfieldCard {
+  pub name: String
+}
+
+fn to_cards(board: HashMap<String, MoxfieldEntry>) -> Vec<Card> {
+  board.into_values().map(|entry| Card::new(entry.quantity, entry.card.name))
+    .collect()
+}
+
+impl MoxfieldDeck {
+  // Commanders play from the command zone, so they count against the maindeck. Companions
+  // start outside the game like sideboard cards do, so they are priced with the sideboard.
+  pub fn into_deck(self, id: String) -> Deck {
+    let mut mainboard = to_cards(self.mainboard);
+    mainboard.append(&mut to_cards(self.commanders));
+
+    let mut sideboard = to_cards(self.sideboard);
+    sideboard.append(&mut to_cards(self.companions));
+
+    let mut deck = Deck::from_cards(id, mainboard, sideboard);
+    if let Some(name) = self.name { deck.set_name(name) }
+    deck
+  }
+}
+
+#[async_trait]
+impl DeckSource for Moxfield {
+  fn name(&self) -> &'static str {
+    "Moxfield"
+  }
+
+  fn url_pattern(&self) -> &'static str {
+    r"^https://(?:www\.)?moxfield\.com/decks/([\w-]+)"
+  }
+
+  async fn retrieve(&self, id: &str) -> Result<String, DreadbotError> {
+    let uri = format!("https://api2.moxfield.com/v2/decks/all/{}", id);
+    fetch_text(self, id, &uri).await
+  }
+
+  fn deck_url(&self, id: &str) -> Option<String> {
+    Some(format!("https://www.moxfield.com/decks/{}", id))
+  }
+
+  fn parse(&self, id: &str, body: &str) -> Result<Deck, DreadbotError> {
+    let moxfield: MoxfieldDeck = serde_json::from_str(body)?;
+    moxfield.into_deck(String::from(id)).require_cards()
+  }
+}
+
+#[test]
+fn test_moxfield_fixture() {
+  let deck = Moxfield.parse("abc123", include_str!("../fixtures/moxfield.json")).unwrap();
+  let names: Vec<&str> = deck.cards().map(|c| c.name.as_str()).collect();
+
+  assert_eq!(names, vec!["Island", "Treasure Hunt", "Zombie Infestation", "Lurrus of the Dream-Den", "Negate"]);
+  assert_eq!(deck.name(), "Zombie Hunt");
+}
@@ -0,0 +1,102 @@
+use unicode_normalization::UnicodeNormalization;
+use unicode_normalization::char::is_combining_mark;
+
+// Every site and export spells names a little differently. Parsers clean names up for display
+// with `display_name`, and every comparison goes through `normalize` so that "Fire/Ice",
+// "fire // ice", "Lim-Dul's Vault" and "Lim-Dûl’s Vault" all land on the same key.
+
+fn replace_typography(name: &str) -> String {
+  name
+    .replace(['\u{2019}', '\u{2018}'], "'")
+    .replace(['\u{201C}', '\u{201D}'], "\"")
+    .replace(['\u{2013}', '\u{2014}'], "-")
+    .replace('Æ', "Ae")
+    .replace('æ', "ae")
+}
+
+fn join_faces(name: &str) -> String {
+  name.split('/')
+    .map(|face| face.split_whitespace().collect::<Vec<&str>>().join(" "))
+    .filter(|face| !face.is_empty())
+    .collect::<Vec<String>>()
+    .join(" // ")
+}
+
+// The name as it should be shown, with faces always separated by " // "
+pub fn display_name(name: &str) -> String {
+  join_faces(&replace_typography(name.trim()))
+}
+
+// Lowercase, accent free, punctuation free key used for every name comparison
+pub fn normalize(name: &str) -> String {
+  let plain: String = replace_typography(name)
+    .nfd()
+    .filter(|c| !is_combining_mark(*c))
+    .collect::<String>()
+    .to_lowercase();
+
+  let stripped: String = plain.chars()
+    .filter(|c| c.is_alphanumeric() || c.is_whitespace() || *c == '/')
+    .collect();
+
+  let key = join_faces(&stripped);
+
+  // Some Un-set names are nothing but punctuation, so keep those as they are
+  if key.is_empty() { join_faces(&plain) } else { key }
+}
+
+// Names match when they are the same card, or when one of them is the front face of the other
+pub fn names_match(a: &str, b: &str) -> bool {
+  let a = normalize(a);
+  let b = normalize(b);
+  if a == b { return true }
+
+  let a_front = a.split(" // ").next().unwrap_or("");
+  let b_front = b.split(" // ").next().unwrap_or("");
+  a == b_front || b == a_front
+}
+
+#[test]
+fn test_display_name() {
+  assert_eq!(display_name("Fire/Ice"), "Fire // Ice");
+  assert_eq!(display_name(" Fire  //  Ice "), "Fire // Ice");
+  assert_eq!(display_name("Lim-Dûl’s Vault"), "Lim-Dûl's Vault");
+  assert_eq!(display_name("Æther Vial"), "Aether Vial");
+}
+
+#[test]
+fn test_normalize() {
+  assert_eq!(normalize("Lim-Dûl's Vault"), normalize("Lim-Dul's Vault"));
+  assert_eq!(normalize("Lim-Dûl’s Vault"), normalize("lim-dul's vault"));
+  assert_eq!(normalize("Fire/Ice"), normalize("Fire // Ice"));
+  assert_eq!(normalize("Æther Vial"), normalize("Aether Vial"));
+  assert_eq!(normalize("Ach! Hans, Run!"), normalize("Ach Hans Run"));
+  assert_eq!(normalize("_____"), "_____");
+  assert_ne!(normalize("Fire // Ice"), normalize("Fire // Fury"));
+}
+
+#[test]
+fn test_names_match() {
+  assert!(names_match("Fire // Ice", "Fire/Ice"));
+  assert!(names_match("fire / ice", "Fire // Ice"));
+  assert!(names_match("Delver of Secrets", "Delver of Secrets // Insectile Aberration"));
+  assert!(names_match("Fire", "Fire // Ice"));
+  assert!(!names_match("Ice", "Fire // Ice"));
+  assert!(!names_match("Fire // Ice", "Fire // Fury"));
+  assert!(!names_match("Island", "Swamp"));
+}
+
+#[test]
+fn test_layout_fixtures() {
+  use super::bulk::PriceIndex;
+  use super::scryfall::ScryfallData;
+
+  let data: Vec<ScryfallData> = serde_json::from_str(include_str!("../fixtures/layouts.json")).unwrap();
+  let index = PriceIndex::from_scryfall_data(data);
+  let cases: Vec<(String, String)> = serde_json::from_str(include_str!("../fixtures/names.json")).unwrap();
+
+  for (input, expected) in cases {
+    let found = index.get(&input).map(|entry| entry.name.clone());
+    assert_eq!(found, Some(expected), "{:?} did not match", input);
+  }
+}
@@ -0,0 +1,67 @@
+use super::deck::Deck;
+use super::error::DreadbotError;
+#[cfg(test)]
+use super::goldfish::Goldfish;
+use super::source::DeckSource;
+use async_trait::async_trait;
+
+pub struct Pasted;
+
+#[async_trait]
+impl DeckSource for Pasted {
+  fn name(&self) -> &'static str {
+    "Pasted list"
+  }
+
+  // The "id" of a pasted list is the contents of the code block itself
+  fn url_pattern(&self) -> &'static str {
+    r"(?s)^```[a-z]*\n?(.*?)```"
+  }
+
+  async fn retrieve(&self, id: &str) -> Result<String, DreadbotError> {
+    Ok(String::from(id))
+  }
+
+  fn deck_url(&self, _id: &str) -> Option<String> {
+    None
+  }
+
+  // Pasted lists follow the Goldfish line rules, so they come out identical to a Goldfish download
+  fn parse(&self, _id: &str, body: &str) -> Result<Deck, DreadbotError> {
+    Deck::parse_goldfish_block(String::from("pasted"), String::from(body.trim()))
+  }
+}
+
+#[tokio::test]
+async fn test_pasted_code_block() {
+  let message = "```\n4 Treasure Hunt\n4 Zombie Infestation\n26 Island\n\n3 Negate\n```";
+  let id = Pasted.extract_id(message).unwrap();
+  let deck = Pasted.parse(&id, &Pasted.retrieve(&id).await.unwrap()).unwrap();
+  let names: Vec<&str> = deck.cards().map(|c| c.name.as_str()).collect();
+
+  assert_eq!(names, vec!["Island", "Treasure Hunt", "Zombie Infestation", "Negate"]);
+}
+
+#[test]
+fn test_pasted_matches_goldfish() {
+  let block = include_str!("../fixtures/goldfish.txt");
+  let message = format!("```\n{}```", block);
+  let id = Pasted.extract_id(&message).unwrap();
+
+  assert_eq!(Pasted.parse(&id, &id).unwrap().to_hash(), Goldfish.parse("10108", block).unwrap().to_hash());
+
+  // Lines the Goldfish path rejects are rejected here too
+  assert!(Pasted.parse("", "4x Treasure Hunt").is_err());
+  assert!(Goldfish.parse("10108", "4x Treasure Hunt").is_err());
+}
+
+#[test]
+fn test_pasted_sideboard_header() {
+  let message = "```text\n4 Treasure Hunt\nSideboard\n3 Negate\n```";
+  let id = Pasted.extract_id(message).unwrap();
+  let deck = Pasted.parse(&id, &id).unwrap();
+  let goldfish = Deck::from_goldfish_block(String::from("pasted"), String::from("4 Treasure Hunt\r\n\r\n3 Negate"));
+
+  assert_eq!(deck.to_hash(), goldfish.to_hash());
+  assert_eq!(deck.cards().last().unwrap().name, "Negate");
+}
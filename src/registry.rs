@@ -0,0 +1,137 @@
+use super::card::Card;
+use super::deck::Deck;
+use rusqlite::{Connection, OptionalExtension, NO_PARAMS, params};
+use std::path::Path;
+
+// A decklist as it was when it was accepted by $$verify
+#[derive(Debug)]
+pub struct Submission {
+  pub hash: String,
+  pub user_id: String,
+  pub user_name: String,
+  pub submitted_at: String,
+  pub source: String,
+  pub mainboard: Vec<Card>,
+  pub sideboard: Vec<Card>
+}
+
+impl Submission {
+  pub fn from_deck(deck: &Deck, user_id: String, user_name: String, submitted_at: String) -> Self {
+    Submission {
+      hash: deck.to_hash(),
+      user_id,
+      user_name,
+      submitted_at,
+      source: String::from(deck.source().unwrap_or("unknown")),
+      mainboard: deck.mainboard().clone(),
+      sideboard: deck.sideboard().clone()
+    }
+  }
+
+  pub fn to_deck(&self) -> Deck {
+    let mut deck = Deck::from_cards(self.hash.clone(), self.mainboard.clone(), self.sideboard.clone());
+    deck.set_source(self.source.clone());
+    deck
+  }
+}
+
+pub struct Registry {
+  connection: Connection
+}
+
+impl Registry {
+  fn from_connection(connection: Connection) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+    connection.execute(
+      "CREATE TABLE IF NOT EXISTS submissions (
+        id INTEGER PRIMARY KEY,
+        hash TEXT NOT NULL,
+        user_id TEXT NOT NULL,
+        user_name TEXT NOT NULL,
+        submitted_at TEXT NOT NULL,
+        source TEXT NOT NULL,
+        mainboard TEXT NOT NULL,
+        sideboard TEXT NOT NULL
+      )",
+      NO_PARAMS
+    )?;
+
+    connection.execute("CREATE INDEX IF NOT EXISTS submissions_hash ON submissions (hash)", NO_PARAMS)?;
+
+    Ok(Registry { connection })
+  }
+
+  pub fn open(path: &Path) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+    Registry::from_connection(Connection::open(path)?)
+  }
+
+  #[cfg(test)]
+  pub fn open_in_memory() -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+    Registry::from_connection(Connection::open_in_memory()?)
+  }
+
+  pub fn record(&self, submission: &Submission) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    self.connection.execute(
+      "INSERT INTO submissions (hash, user_id, user_name, submitted_at, source, mainboard, sideboard)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+      params![
+        submission.hash,
+        submission.user_id,
+        submission.user_name,
+        submission.submitted_at,
+        submission.source,
+        serde_json::to_string(&submission.mainboard)?,
+        serde_json::to_string(&submission.sideboard)?
+      ]
+    )?;
+
+    Ok(())
+  }
+
+  // The most recent submission with the given hash
+  pub fn lookup(&self, hash: &str) -> Result<Option<Submission>, Box<dyn std::error::Error + Send + Sync>> {
+    let row: Option<(String, String, String, String, String, String, String)> = self.connection.query_row(
+      "SELECT hash, user_id, user_name, submitted_at, source, mainboard, sideboard
+        FROM submissions WHERE hash = ?1 ORDER BY id DESC LIMIT 1",
+      params![hash],
+      |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?, row.get(5)?, row.get(6)?))
+    ).optional()?;
+
+    let (hash, user_id, user_name, submitted_at, source, mainboard, sideboard) = match row {
+      Some(row) => row,
+      None => return Ok(None)
+    };
+
+    Ok(Some(Submission {
+      hash,
+      user_id,
+      user_name,
+      submitted_at,
+      source,
+      mainboard: serde_json::from_str(&mainboard)?,
+      sideboard: serde_json::from_str(&sideboard)?
+    }))
+  }
+}
+
+#[test]
+fn test_record_and_lookup() {
+  let registry = Registry::open_in_memory().unwrap();
+  let mut deck = Deck::from_goldfish_block(String::from("10108"), String::from("4 Treasure Hunt\r\n26 Island\r\n\r\n3 Negate"));
+  deck.set_source(String::from("https://www.mtggoldfish.com/deck/10108"));
+
+  let submission = Submission::from_deck(&deck, String::from("1234"), String::from("dreadful"), String::from("2019-10-01T20:00:00+00:00"));
+  registry.record(&submission).unwrap();
+
+  let found = registry.lookup(&deck.to_hash()).unwrap().unwrap();
+  assert_eq!(found.user_name, "dreadful");
+  assert_eq!(found.source, "https://www.mtggoldfish.com/deck/10108");
+  assert_eq!(found.mainboard.len(), 2);
+  assert_eq!(found.sideboard.first().unwrap().name, "Negate");
+  assert_eq!(found.to_deck().to_hash(), deck.to_hash());
+}
+
+#[test]
+fn test_lookup_missing_hash() {
+  let registry = Registry::open_in_memory().unwrap();
+  assert!(registry.lookup("000000000000").unwrap().is_none());
+}
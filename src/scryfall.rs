@@ -1,7 +1,17 @@
 extern crate serde_derive;
-use serde::{Deserialize};
-use super::card::{Card, Cents};
+use reqwest::StatusCode;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use serde::{Deserialize, Serialize};
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
+use tokio::sync::{Mutex, Semaphore};
+use tokio::time::{Instant, sleep};
+use super::card::{Card, Cents, Printing, parse_cents};
 use super::deck::Deck;
+use super::error::DreadbotError;
+use super::format::{BasicLandPolicy, PricePolicy};
 
 #[derive(Deserialize, Debug)]
 pub struct ScryfallResponse {
@@ -13,7 +23,26 @@ pub struct ScryfallResponse {
 pub struct ScryfallData {
   pub name: String,
   pub prices: ScryfallPrices,
-  pub card_faces: Option<Vec<ScryfallCardFaces>>
+  pub card_faces: Option<Vec<ScryfallCardFaces>>,
+  #[serde(default)]
+  pub oversized: bool,
+  #[serde(default)]
+  pub digital: bool,
+  #[serde(default)]
+  pub border_color: String,
+  #[serde(default)]
+  pub legalities: HashMap<String, String>,
+  #[serde(default)]
+  pub set: String,
+  #[serde(default)]
+  pub collector_number: String,
+  #[serde(default)]
+  pub scryfall_uri: String
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ScryfallCatalog {
+  pub data: Vec<String>
 }
 
 #[derive(Deserialize, Debug)]
@@ -27,220 +56,422 @@ pub struct ScryfallPrices {
   pub usd_foil: Option<String>
 }
 
-#[derive(Debug)]
+// The cheapest nonfoil and foil printing of a card
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct PricingSource {
   pub name: String,
-  pub price: Cents,
-  pub front_name: String
+  pub front_name: String,
+  pub nonfoil: Option<Printing>,
+  pub foil: Option<Printing>,
+  // Format name to Scryfall legality status, e.g. "modern" => "banned"
+  #[serde(default)]
+  pub legalities: HashMap<String, String>
 }
 
-const BASIC_LAND_NAMES: &'static [&'static str] = &[
-  "Swamp",
-  "Island",
-  "Forest",
-  "Mountain",
-  "Plains"
-];
+// Keeps the earlier printing on ties, so results follow Scryfall's ordering
+fn cheapest<'a>(a: Option<&'a Printing>, b: Option<&'a Printing>) -> Option<&'a Printing> {
+  match (a, b) {
+    (Some(a), Some(b)) => Some(if b.price < a.price { b } else { a }),
+    (Some(a), None) => Some(a),
+    (None, b) => b
+  }
+}
+
+impl PricingSource {
+  pub fn printing(&self, policy: &PricePolicy) -> Option<&Printing> {
+    if policy.allow_foil { cheapest(self.nonfoil.as_ref(), self.foil.as_ref()) } else { self.nonfoil.as_ref() }
+  }
+
+  #[cfg(test)]
+  pub fn price(&self, policy: &PricePolicy) -> Option<Cents> {
+    self.printing(policy).map(|printing| printing.price)
+  }
+}
 
 fn format_scryfall_param(card: &Card) -> String {
   format!("!\"{}\"", card.name)
 }
 
-fn get_nonfoil_price(data: &ScryfallData) -> Option<Cents> {
-    let str_price = match &data.prices.usd {
-      Some(price) => price,
-      None => return None
-    };
+// Mirrors the filters of the live query: -is:oversized -is:digital -border:gold usd>0
+pub fn is_paper_printing(data: &ScryfallData) -> bool {
+  let has_usd_price = match get_nonfoil_price(data) {
+    Some(price) => price > 0,
+    None => false
+  };
 
-    let price = match str_price.parse::<f32>() {
-      Ok(p32) => (p32 * 100f32) as Cents,
-      _ => return None
-    };
+  !data.oversized && !data.digital && data.border_color != "gold" && has_usd_price
+}
 
-    Some(price)
+fn get_nonfoil_price(data: &ScryfallData) -> Option<Cents> {
+  data.prices.usd.as_ref().and_then(|price| parse_cents(price))
 }
 
 fn get_foil_price(data: &ScryfallData) -> Option<Cents> {
-    let str_price = match &data.prices.usd_foil {
-      Some(price) => price,
-      None => return None
-    };
-
-    let price = match str_price.parse::<f32>() {
-      Ok(p32) => (p32 * 100f32) as Cents,
-      _ => return None
-    };
-
-    Some(price)
+  data.prices.usd_foil.as_ref().and_then(|price| parse_cents(price))
 }
 
-fn get_price(data: &ScryfallData) -> Option<Cents> {
-  let nonfoil_price = get_nonfoil_price(data);
-  let foil_price =  get_foil_price(data);
-
-  match (nonfoil_price, foil_price) {
-    (Some(nonfoil), Some(foil)) => Some(std::cmp::min(nonfoil, foil)),
-    (Some(nonfoil), None) => Some(nonfoil),
-    (None, Some(foil)) => Some(foil),
-    _ => None
-  }
+fn to_printing(data: &ScryfallData, price: Option<Cents>, foil: bool) -> Option<Printing> {
+  Some(Printing {
+    price: price?,
+    set: data.set.to_uppercase(),
+    collector_number: data.collector_number.clone(),
+    scryfall_uri: data.scryfall_uri.clone(),
+    foil
+  })
 }
 
-fn reduce_pricing(entries: Vec<ScryfallData>) -> Vec<PricingSource> {
+pub fn reduce_pricing(entries: Vec<ScryfallData>) -> Vec<PricingSource> {
   let mut prices: Vec<PricingSource> = Vec::new();
+  let mut positions: HashMap<String, usize> = HashMap::new();
 
   for entry in entries {
-    let price = match get_price(&entry) {
-      Some(price) => price,
-      None => continue
-    };
-
-    let previous_entry = prices.iter_mut().find(|ps| ps.name == entry.name);
+    let nonfoil = to_printing(&entry, get_nonfoil_price(&entry), false);
+    let foil = to_printing(&entry, get_foil_price(&entry), true);
+    if nonfoil.is_none() && foil.is_none() { continue }
 
-    // If it exists, update if the new price is lower
-    if let Some(previous_price) = previous_entry {
-      if price < previous_price.price {
-        previous_price.price = price;
-      }
+    // If it exists, keep the cheaper printing of each kind
+    if let Some(position) = positions.get(&entry.name) {
+      let previous_price = &mut prices[*position];
+      previous_price.nonfoil = cheapest(previous_price.nonfoil.as_ref(), nonfoil.as_ref()).cloned();
+      previous_price.foil = cheapest(previous_price.foil.as_ref(), foil.as_ref()).cloned();
 
     // Otherwise add it
     } else {
       // For double sided cards, we need to save their front name for goldfish
       let front_name = if let Some(faces) = entry.card_faces {
-        faces.get(0).unwrap().name.clone()
+        faces.first().unwrap().name.clone()
       } else {
         entry.name.clone()
       };
 
-      prices.push(PricingSource { name: entry.name, price: price, front_name: front_name });
+      positions.insert(entry.name.clone(), prices.len());
+      prices.push(PricingSource {
+        name: entry.name,
+        front_name,
+        nonfoil,
+        foil,
+        legalities: entry.legalities
+      });
     }
   }
 
   prices
 }
 
-pub fn request_pricing(deck: &Deck) -> Result<Vec<PricingSource>, Box<dyn std::error::Error>> {
-  let mut name_params = String::new();
-  let mut first_flag = true;
-  for card in deck.cards() {
-    // Advance the first flag if true
-    let is_first = first_flag;
-    first_flag = false;
+// Scryfall asks for 50-100ms between requests
+const REQUEST_SPACING: Duration = Duration::from_millis(100);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+const MAX_ATTEMPTS: u32 = 4;
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+// Concurrent deck checks share this many Scryfall requests in flight
+const MAX_IN_FLIGHT: usize = 2;
+
+fn unavailable<T: fmt::Display>(reason: T) -> DreadbotError {
+  DreadbotError::PricingUnavailable(reason.to_string())
+}
+
+// Rate limits and server errors usually clear up on their own
+fn is_transient(status: StatusCode) -> bool {
+  status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+fn backoff_delay(attempt: u32) -> Duration {
+  INITIAL_BACKOFF * 2u32.pow(attempt - 1)
+}
+
+// Seconds from a Retry-After header, when Scryfall sends one with a 429
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+  let seconds = headers.get(RETRY_AFTER)?.to_str().ok()?.parse::<u64>().ok()?;
+  Some(Duration::from_secs(seconds))
+}
 
-    // If it is a basic, do not add it to the list. This returns hundreds of cards each
-    if let Some(_) = BASIC_LAND_NAMES.iter().find(|name| *name == &card.name) {
-      continue;
+// Scryfall's own estimate wins over exponential backoff
+fn retry_delay(headers: &HeaderMap, attempt: u32) -> Duration {
+  retry_after(headers).unwrap_or_else(|| backoff_delay(attempt))
+}
+
+// One HTTP client shared by every Scryfall request, which keeps requests spaced out and capped
+pub struct ScryfallClient {
+  client: reqwest::Client,
+  last_request: Mutex<Option<Instant>>,
+  in_flight: Semaphore
+}
+
+impl ScryfallClient {
+  pub fn new() -> Result<Self, DreadbotError> {
+    let client = reqwest::Client::builder()
+      .timeout(REQUEST_TIMEOUT)
+      .build()
+      .map_err(unavailable)?;
+
+    Ok(ScryfallClient { client, last_request: Mutex::new(None), in_flight: Semaphore::new(MAX_IN_FLIGHT) })
+  }
+
+  async fn wait_turn(&self) {
+    let mut last_request = self.last_request.lock().await;
+    if let Some(elapsed) = last_request.map(|at| at.elapsed()) {
+      if elapsed < REQUEST_SPACING { sleep(REQUEST_SPACING - elapsed).await }
     }
 
-    // Add to it
-    if !is_first { name_params += " OR "; }
-    name_params += &format_scryfall_param(card)
+    *last_request = Some(Instant::now());
+  }
+
+  // Retries timeouts, rate limits and server errors with exponential backoff. None means a 404.
+  async fn get<T: DeserializeOwned>(&self, url: &str) -> Result<Option<T>, DreadbotError> {
+    let mut attempt = 1;
+
+    loop {
+      // The permit is only held for the request itself, not while backing off
+      let (reason, delay) = {
+        let _permit = self.in_flight.acquire().await.map_err(unavailable)?;
+        self.wait_turn().await;
+
+        match self.client.get(url).send().await {
+          Ok(response) => {
+            let status = response.status();
+            if status.is_success() { return response.json().await.map(Some).map_err(unavailable) }
+            if status == StatusCode::NOT_FOUND { return Ok(None) }
+            if !is_transient(status) { return Err(unavailable(format!("unexpected status {}", status))) }
+
+            (format!("status {}", status), retry_delay(response.headers(), attempt))
+          },
+          Err(why) => (why.to_string(), backoff_delay(attempt))
+        }
+      };
+
+      if attempt == MAX_ATTEMPTS {
+        return Err(unavailable(format!("{} after {} attempts", reason, attempt)));
+      }
+
+      sleep(delay).await;
+      attempt += 1;
+    }
   }
 
-  // If there are no names, the query returns all cards. Thats bad! Return now.
-  if name_params.is_empty() { return Ok(Vec::new()); }
+  pub async fn request_pricing(&self, deck: &Deck, policy: &PricePolicy) -> Result<Vec<PricingSource>, DreadbotError> {
+    let mut name_params = String::new();
+    for card in deck.cards() {
+      // Unless they are priced, do not add basics to the list. This returns hundreds of cards each
+      if card.is_basic_land() && policy.basic_lands == BasicLandPolicy::Free {
+        continue;
+      }
 
-  // Start a list of ScryfallData in case there are multiple requests
-  let mut data: Vec<ScryfallData> = Vec::new();
+      // Add to it
+      if !name_params.is_empty() { name_params += " OR "; }
+      name_params += &format_scryfall_param(card)
+    }
 
-  // Build the initial query
-  let query =
-    format!("https://api.scryfall.com/cards/search?unique=prints&q=-is:oversized -is:digital -border:gold usd>0 ({})", name_params)
-      .replace(" ", "%20")
-      .replace("\"", "%22");
+    // If there are no names, the query returns all cards. Thats bad! Return now.
+    if name_params.is_empty() { return Ok(Vec::new()); }
 
-  // Send it and merge
-  let mut response: ScryfallResponse =
-    reqwest::Client::new()
-      .get(&query)
-      .send()?
-      .json()?;
+    // Start a list of ScryfallData in case there are multiple requests
+    let mut data: Vec<ScryfallData> = Vec::new();
 
-  data.append(&mut response.data);
+    // Build the initial query
+    let query =
+      format!("https://api.scryfall.com/cards/search?unique=prints&q=-is:oversized -is:digital -border:gold usd>0 ({})", name_params)
+        .replace(" ", "%20")
+        .replace("\"", "%22");
 
-  // Consume until there is no more
-  while let Some(next_url) = response.next_page {
-    response =
-      reqwest::Client::new()
-        .get(&next_url)
-        .send()?
-        .json()?;
+    // Send it and merge. A 404 means none of the names matched a card.
+    let mut response: ScryfallResponse = match self.get(&query).await? {
+      Some(response) => response,
+      None => return Ok(Vec::new())
+    };
 
     data.append(&mut response.data);
+
+    // Consume until there is no more
+    while let Some(next_url) = response.next_page {
+      response = self.get(&next_url).await?.ok_or_else(|| unavailable("a result page went missing"))?;
+      data.append(&mut response.data);
+    }
+
+    Ok(reduce_pricing(data))
   }
 
-  Ok(reduce_pricing(data))
+  // Card names Scryfall considers close to the given one, for cards that did not match
+  pub async fn request_suggestions(&self, name: &str) -> Result<Vec<String>, DreadbotError> {
+    let url = reqwest::Url::parse_with_params("https://api.scryfall.com/cards/autocomplete", &[("q", name)])
+      .map_err(unavailable)?;
+
+    let catalog: Option<ScryfallCatalog> = self.get(url.as_str()).await?;
+    Ok(catalog.map(|catalog| catalog.data).unwrap_or_default())
+  }
 }
 
-#[test]
-fn test_api_call() {
+#[cfg(test)]
+pub fn test_source(name: &str, nonfoil: Option<Cents>, foil: Option<Cents>) -> PricingSource {
+  let printing = |price: Cents, foil: bool| Printing {
+    price,
+    set: String::from("TST"),
+    collector_number: String::from("1"),
+    scryfall_uri: String::new(),
+    foil
+  };
+
+  PricingSource {
+    name: String::from(name),
+    front_name: String::from(name),
+    nonfoil: nonfoil.map(|price| printing(price, false)),
+    foil: foil.map(|price| printing(price, true)),
+    legalities: HashMap::new()
+  }
+}
+
+#[tokio::test]
+async fn test_api_call() {
     let zombie_hunt = "4 Treasure Hunt\r\n4 Zombie Infestation\r\n26 Island\r\n26 Swamp";
     let deck = Deck::from_goldfish_block(String::from("10108"), String::from(zombie_hunt));
 
-    let scryfall_resp = request_pricing(&deck).unwrap();
+    let scryfall_resp = ScryfallClient::new().unwrap().request_pricing(&deck, &PricePolicy::default()).await.unwrap();
     for item in &scryfall_resp {
-        println!("{:?} costs {:?}", item.name, item.price);
+        println!("{:?} costs {:?}", item.name, item.nonfoil);
     }
 
-    assert_eq!(scryfall_resp.get(0).is_some(), true);
+    assert!(!scryfall_resp.is_empty());
 }
 
 #[test]
 fn test_reduce_pricing() {
-  let mut scryfall_mock: Vec<ScryfallData> = Vec::new();
-
-  scryfall_mock.push(ScryfallData{
-    name: String::from("Island"),
-    card_faces: None,
-    prices: ScryfallPrices {
-      usd: Some(String::from("1.00")),
-      usd_foil: Some(String::from("10.00"))
-    }
-  });
-
-  scryfall_mock.push(ScryfallData{
-    name: String::from("Island"),
-    card_faces: None,
-    prices: ScryfallPrices {
-      usd: Some(String::from("0.50")),
-      usd_foil: Some(String::from("10.00"))
-    }
-  });
-
-  scryfall_mock.push(ScryfallData{
-    name: String::from("Island"),
-    card_faces: None,
-    prices: ScryfallPrices {
-      usd: Some(String::from("2.00")),
-      usd_foil: Some(String::from("10.00"))
+  let scryfall_mock: Vec<ScryfallData> = vec![
+    ScryfallData {
+      name: String::from("Island"),
+      card_faces: None,
+      oversized: false,
+      digital: false,
+      border_color: String::from("black"),
+      legalities: HashMap::new(),
+      set: String::from("lea"),
+      collector_number: String::from("288"),
+      scryfall_uri: "https://scryfall.com/card/lea/288/island".to_string(),
+      prices: ScryfallPrices {
+        usd: Some(String::from("1.00")),
+        usd_foil: Some(String::from("10.00"))
+      }
+    },
+    ScryfallData {
+      name: String::from("Island"),
+      card_faces: None,
+      oversized: false,
+      digital: false,
+      border_color: String::from("black"),
+      legalities: HashMap::new(),
+      set: String::from("lea"),
+      collector_number: String::from("289"),
+      scryfall_uri: "https://scryfall.com/card/lea/289/island".to_string(),
+      prices: ScryfallPrices {
+        usd: Some(String::from("0.50")),
+        usd_foil: Some(String::from("10.00"))
+      }
+    },
+    ScryfallData {
+      name: String::from("Island"),
+      card_faces: None,
+      oversized: false,
+      digital: false,
+      border_color: String::from("black"),
+      legalities: HashMap::new(),
+      set: String::from("lea"),
+      collector_number: String::from("290"),
+      scryfall_uri: "https://scryfall.com/card/lea/290/island".to_string(),
+      prices: ScryfallPrices {
+        usd: Some(String::from("2.00")),
+        usd_foil: Some(String::from("10.00"))
+      }
     }
-  });
+  ];
 
   let reduced_prices = reduce_pricing(scryfall_mock);
   assert_eq!(reduced_prices.len(), 1);
-  assert_eq!(reduced_prices.get(0).unwrap().name, "Island");
-  assert_eq!(reduced_prices.get(0).unwrap().price, 50 as Cents);
+  assert_eq!(reduced_prices.first().unwrap().name, "Island");
+  let nonfoil = reduced_prices.first().unwrap().nonfoil.clone().unwrap();
+  assert_eq!(nonfoil.price, 50 as Cents);
+  assert_eq!(nonfoil.describe(), "LEA #289 nonfoil");
+  assert_eq!(nonfoil.scryfall_uri, "https://scryfall.com/card/lea/289/island");
+
+  // All three foils cost the same, so the first one wins
+  let foil = reduced_prices.first().unwrap().foil.clone().unwrap();
+  assert_eq!(foil.price, 1000 as Cents);
+  assert_eq!(foil.describe(), "LEA #288 foil");
 }
 
 #[test]
-fn test_multiple_requests() {
+fn test_price_policy() {
+  let entry = test_source("Lightning Bolt", Some(110), Some(75));
+
+  let mut policy = PricePolicy::default();
+  assert_eq!(entry.price(&policy), Some(75));
+  assert!(entry.printing(&policy).unwrap().foil);
+
+  policy.allow_foil = false;
+  assert_eq!(entry.price(&policy), Some(110));
+  assert!(!entry.printing(&policy).unwrap().foil);
+}
+
+#[tokio::test]
+async fn test_multiple_requests() {
   let block: String = String::from("22 Air Elemental\r\n27 Counterspell\r\n28 Dark Ritual\r\n27 Disenchant\r\n21 Evolving Wilds\r\n25 Fireball\r\n34 Giant Growth\r\n25 Llanowar Elves\r\n21 Pacifism\r\n27 Serra Angel\r\n20 Shatter\r\n22 Shivan Dragon\r\n23 Stone Rain\r\n21 Swords to Plowshares\r\n21 Terror\r\n20 Unsummon");
   let deck = Deck::from_goldfish_block(String::from("10108"), block);
   println!("{:?}", deck);
 
-  let scryfall_resp = request_pricing(&deck).unwrap();
+  let scryfall_resp = ScryfallClient::new().unwrap().request_pricing(&deck, &PricePolicy::default()).await.unwrap();
   println!("{:?}", scryfall_resp);
 
   assert_eq!(scryfall_resp.len(), 16);
 }
 
-#[test]
-fn test_double_sided_card_requests() {
+#[tokio::test]
+async fn test_double_sided_card_requests() {
   let block: String = String::from("1 Delver of Secrets");
   let deck = Deck::from_goldfish_block(String::from("10108"), block);
 
-  let scryfall_resp = request_pricing(&deck).unwrap();
+  let scryfall_resp = ScryfallClient::new().unwrap().request_pricing(&deck, &PricePolicy::default()).await.unwrap();
+
+  assert_eq!(scryfall_resp.first().unwrap().name, "Delver of Secrets // Insectile Aberration");
+  assert_eq!(scryfall_resp.first().unwrap().front_name, "Delver of Secrets");
+}
+
+#[test]
+fn test_backoff() {
+  assert_eq!(backoff_delay(1), Duration::from_millis(500));
+  assert_eq!(backoff_delay(3), Duration::from_millis(2000));
+
+  assert!(is_transient(StatusCode::TOO_MANY_REQUESTS));
+  assert!(is_transient(StatusCode::SERVICE_UNAVAILABLE));
+  assert!(!is_transient(StatusCode::BAD_REQUEST));
+}
+
+#[test]
+fn test_retry_delay() {
+  let mut headers = HeaderMap::new();
+  assert_eq!(retry_delay(&headers, 2), Duration::from_millis(1000));
+
+  headers.insert(RETRY_AFTER, "3".parse().unwrap());
+  assert_eq!(retry_delay(&headers, 2), Duration::from_secs(3));
+
+  // HTTP dates are not used by Scryfall, so they fall back to backoff
+  headers.insert(RETRY_AFTER, "Wed, 21 Oct 2015 07:28:00 GMT".parse().unwrap());
+  assert_eq!(retry_delay(&headers, 1), Duration::from_millis(500));
+}
+
+#[tokio::test]
+async fn test_request_spacing() {
+  let client = ScryfallClient::new().unwrap();
+  let started = Instant::now();
+
+  client.wait_turn().await;
+  client.wait_turn().await;
+  client.wait_turn().await;
+
+  assert!(started.elapsed() >= REQUEST_SPACING * 2);
+  assert_eq!(client.in_flight.available_permits(), MAX_IN_FLIGHT);
+}
+
+#[tokio::test]
+async fn test_free_basics_skip_the_request() {
+  let deck = Deck::from_goldfish_block(String::from("10108"), String::from("30 Island\r\n30 Swamp"));
+  let scryfall_resp = ScryfallClient::new().unwrap().request_pricing(&deck, &PricePolicy::default()).await.unwrap();
 
-  assert_eq!(scryfall_resp.get(0).unwrap().name, "Delver of Secrets // Insectile Aberration");
-  assert_eq!(scryfall_resp.get(0).unwrap().front_name, "Delver of Secrets");
+  assert!(scryfall_resp.is_empty());
 }
@@ -0,0 +1,159 @@
+extern crate serde_derive;
+use serde::{Deserialize, Serialize};
+use super::bulk::PriceIndex;
+use super::scryfall::PricingSource;
+#[cfg(test)]
+use super::scryfall::test_source;
+use std::fs;
+use std::path::PathBuf;
+
+const ACTIVE_FILE: &str = "ACTIVE";
+
+// A named set of prices frozen from one bulk data file, so a season is judged against the same numbers.
+// The date is when Scryfall published that bulk data.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Snapshot {
+  pub name: String,
+  pub date: String,
+  pub prices: Vec<PricingSource>
+}
+
+impl Snapshot {
+  pub fn to_index(&self) -> PriceIndex {
+    PriceIndex::from_pricing(self.prices.clone())
+  }
+}
+
+pub struct SnapshotStore {
+  directory: PathBuf
+}
+
+impl SnapshotStore {
+  pub fn new(directory: PathBuf) -> Self {
+    SnapshotStore { directory }
+  }
+
+  fn path_for(&self, name: &str) -> PathBuf {
+    self.directory.join(format!("{}.json", name))
+  }
+
+  pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+  }
+
+  pub fn create(&self, name: &str, date: &str, prices: Vec<PricingSource>) -> Result<Snapshot, Box<dyn std::error::Error + Send + Sync>> {
+    // A snapshot never changes once taken, least of all the one decks are being priced against
+    let path = self.path_for(name);
+    if path.exists() { return Err(format!("a snapshot named {} already exists", name).into()) }
+
+    let snapshot = Snapshot { name: String::from(name), date: String::from(date), prices };
+
+    fs::create_dir_all(&self.directory)?;
+    fs::write(path, serde_json::to_string(&snapshot)?)?;
+
+    Ok(snapshot)
+  }
+
+  pub fn load(&self, name: &str) -> Result<Snapshot, Box<dyn std::error::Error + Send + Sync>> {
+    let contents = fs::read_to_string(self.path_for(name))?;
+    Ok(serde_json::from_str(&contents)?)
+  }
+
+  // Returns the name and date of every stored snapshot, oldest first
+  pub fn list(&self) -> Result<Vec<(String, String)>, Box<dyn std::error::Error + Send + Sync>> {
+    let mut snapshots: Vec<(String, String)> = Vec::new();
+    if !self.directory.exists() { return Ok(snapshots) }
+
+    for entry in fs::read_dir(&self.directory)? {
+      let path = entry?.path();
+      if path.extension().map(|e| e != "json").unwrap_or(true) { continue }
+
+      let snapshot: Snapshot = serde_json::from_str(&fs::read_to_string(&path)?)?;
+      snapshots.push((snapshot.name, snapshot.date));
+    }
+
+    snapshots.sort_by(|a, b| a.1.cmp(&b.1).then(a.0.cmp(&b.0)));
+    Ok(snapshots)
+  }
+
+  pub fn activate(&self, name: &str) -> Result<Snapshot, Box<dyn std::error::Error + Send + Sync>> {
+    let snapshot = self.load(name)?;
+    fs::write(self.directory.join(ACTIVE_FILE), name)?;
+
+    Ok(snapshot)
+  }
+
+  pub fn deactivate(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let active_path = self.directory.join(ACTIVE_FILE);
+    if active_path.exists() { fs::remove_file(active_path)?; }
+
+    Ok(())
+  }
+
+  pub fn active(&self) -> Result<Option<Snapshot>, Box<dyn std::error::Error + Send + Sync>> {
+    let active_path = self.directory.join(ACTIVE_FILE);
+    if !active_path.exists() { return Ok(None) }
+
+    let name = fs::read_to_string(active_path)?;
+    Ok(Some(self.load(name.trim())?))
+  }
+}
+
+#[cfg(test)]
+fn test_store(name: &str) -> SnapshotStore {
+  let directory = std::env::temp_dir().join(format!("dreadbot-snapshots-{}-{}", name, std::process::id()));
+  let _ = fs::remove_dir_all(&directory);
+
+  SnapshotStore::new(directory)
+}
+
+#[test]
+fn test_snapshot_round_trip() {
+  let store = test_store("round-trip");
+  let prices = vec![test_source("Island", Some(10), None)];
+
+  store.create("season-1", "2019-10-01", prices).unwrap();
+  let snapshot = store.load("season-1").unwrap();
+
+  assert_eq!(snapshot.date, "2019-10-01");
+  assert_eq!(snapshot.to_index().get("Island").unwrap().nonfoil.as_ref().unwrap().price, 10);
+}
+
+#[test]
+fn test_snapshot_names_are_not_reused() {
+  let store = test_store("reused");
+  store.create("season-1", "2019-10-01", vec![test_source("Island", Some(10), None)]).unwrap();
+
+  assert!(store.create("season-1", "2020-01-01", Vec::new()).is_err());
+
+  let snapshot = store.load("season-1").unwrap();
+  assert_eq!(snapshot.date, "2019-10-01");
+  assert_eq!(snapshot.prices.len(), 1);
+}
+
+#[test]
+fn test_snapshot_activation() {
+  let store = test_store("activation");
+  store.create("season-2", "2020-01-01", Vec::new()).unwrap();
+  store.create("season-1", "2019-10-01", Vec::new()).unwrap();
+
+  assert!(store.active().unwrap().is_none());
+
+  store.activate("season-1").unwrap();
+  assert_eq!(store.active().unwrap().unwrap().name, "season-1");
+  assert_eq!(store.list().unwrap(), vec![
+    (String::from("season-1"), String::from("2019-10-01")),
+    (String::from("season-2"), String::from("2020-01-01"))
+  ]);
+
+  store.deactivate().unwrap();
+  assert!(store.active().unwrap().is_none());
+  assert!(store.activate("missing").is_err());
+}
+
+#[test]
+fn test_snapshot_names() {
+  assert!(SnapshotStore::is_valid_name("season-1_2019"));
+  assert!(!SnapshotStore::is_valid_name("../ACTIVE"));
+  assert!(!SnapshotStore::is_valid_name(""));
+}
@@ -0,0 +1,81 @@
+use super::archidekt::Archidekt;
+use super::deck::Deck;
+use super::error::DreadbotError;
+use super::goldfish::Goldfish;
+use super::moxfield::Moxfield;
+use super::pasted::Pasted;
+use super::tappedout::TappedOut;
+use async_trait::async_trait;
+use regex::Regex;
+use reqwest::StatusCode;
+
+#[async_trait]
+pub trait DeckSource: Sync {
+  // Site name shown to players when something goes wrong
+  fn name(&self) -> &'static str;
+
+  // Regex for the URLs this source recognizes. The first capture group must be the deck id.
+  fn url_pattern(&self) -> &'static str;
+
+  async fn retrieve(&self, id: &str) -> Result<String, DreadbotError>;
+
+  fn parse(&self, id: &str, body: &str) -> Result<Deck, DreadbotError>;
+
+  // Canonical link back to the deck, if the source has one
+  fn deck_url(&self, id: &str) -> Option<String>;
+
+  fn extract_id(&self, input: &str) -> Option<String> {
+    let regex = Regex::new(self.url_pattern()).unwrap();
+    let captures = regex.captures(input)?;
+
+    captures.get(1).map(|c| String::from(c.as_str()))
+  }
+}
+
+// Downloads a deck page, telling apart decks that do not exist, private decks and site outages
+pub async fn fetch_text(source: &dyn DeckSource, id: &str, uri: &str) -> Result<String, DreadbotError> {
+  let unavailable = |reason: String| DreadbotError::SourceUnavailable { source: String::from(source.name()), reason };
+
+  let response = reqwest::get(uri).await.map_err(|why| unavailable(why.to_string()))?;
+  match response.status() {
+    status if status.is_success() => response.text().await.map_err(|why| unavailable(why.to_string())),
+    StatusCode::NOT_FOUND => Err(DreadbotError::SourceNotFound { source: String::from(source.name()), id: String::from(id) }),
+    StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Err(DreadbotError::SourcePrivate { source: String::from(source.name()), id: String::from(id) }),
+    status => Err(unavailable(format!("status {}", status)))
+  }
+}
+
+pub static SOURCES: &[&dyn DeckSource] = &[
+  &Goldfish,
+  &Moxfield,
+  &Archidekt,
+  &TappedOut,
+  &Pasted
+];
+
+pub fn find_source(input: &str) -> Option<(&'static dyn DeckSource, String)> {
+  for source in SOURCES {
+    if let Some(id) = source.extract_id(input) {
+      return Some((*source, id));
+    }
+  }
+
+  None
+}
+
+#[test]
+fn test_find_source() {
+  let (_, id) = find_source("https://www.mtggoldfish.com/deck/10108#paper").unwrap();
+  assert_eq!(id, "10108");
+
+  let (_, id) = find_source("https://www.moxfield.com/decks/aBc-12_3").unwrap();
+  assert_eq!(id, "aBc-12_3");
+
+  let (_, id) = find_source("https://archidekt.com/decks/55432/zombie_hunt").unwrap();
+  assert_eq!(id, "55432");
+
+  let (_, id) = find_source("https://tappedout.net/mtg-decks/zombie-hunt/").unwrap();
+  assert_eq!(id, "zombie-hunt");
+
+  assert!(find_source("https://example.com/deck/10108").is_none());
+}
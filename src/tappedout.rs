@@ -0,0 +1,39 @@
+use super::deck::Deck;
+use super::error::DreadbotError;
+use super::source::{DeckSource, fetch_text};
+use async_trait::async_trait;
+
+pub struct TappedOut;
+
+#[async_trait]
+impl DeckSource for TappedOut {
+  fn name(&self) -> &'static str {
+    "TappedOut"
+  }
+
+  fn url_pattern(&self) -> &'static str {
+    r"^https://(?:www\.)?tappedout\.net/mtg-decks/([\w-]+)"
+  }
+
+  async fn retrieve(&self, id: &str) -> Result<String, DreadbotError> {
+    let uri = format!("https://tappedout.net/mtg-decks/{}/?fmt=txt", id);
+    fetch_text(self, id, &uri).await
+  }
+
+  fn deck_url(&self, id: &str) -> Option<String> {
+    Some(format!("https://tappedout.net/mtg-decks/{}/", id))
+  }
+
+  // The text export is the same quantity-name format Goldfish uses, with a "Sideboard:" header
+  fn parse(&self, id: &str, body: &str) -> Result<Deck, DreadbotError> {
+    Deck::parse_goldfish_block(String::from(id), String::from(body))
+  }
+}
+
+#[test]
+fn test_tappedout_fixture() {
+  let deck = TappedOut.parse("zombie-hunt", include_str!("../fixtures/tappedout.txt")).unwrap();
+  let names: Vec<&str> = deck.cards().map(|c| c.name.as_str()).collect();
+
+  assert_eq!(names, vec!["Island", "Treasure Hunt", "Zombie Infestation", "Negate"]);
+}
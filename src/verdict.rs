@@ -0,0 +1,159 @@
+use super::card::{Cents, format_cents};
+use super::deck::Deck;
+use super::format::Format;
+#[cfg(test)]
+use super::scryfall::test_source;
+#[cfg(test)]
+use super::format::PricePolicy;
+
+// The outcome of checking a deck against a format, shared by the text and embed replies
+#[derive(Debug)]
+pub struct Verdict {
+  pub deck_name: String,
+  pub source: Option<String>,
+  pub hash: String,
+  pub maindeck_price: Cents,
+  pub sideboard_price: Cents,
+  pub maindeck_limit: Cents,
+  pub sideboard_limit: Cents,
+  pub violations: Vec<String>,
+  // One line per card that could not be priced, with suggestions when there are any
+  pub unmatched: Vec<String>
+}
+
+impl Verdict {
+  pub fn new(deck: &Deck, format: &Format, unmatched: Vec<String>) -> Self {
+    Verdict {
+      deck_name: String::from(deck.name()),
+      source: deck.source().map(String::from),
+      hash: deck.to_hash(),
+      maindeck_price: deck.mainboard_pricing(),
+      sideboard_price: deck.sideboard_pricing(),
+      maindeck_limit: format.maindeck_limit,
+      sideboard_limit: format.sideboard_limit,
+      violations: deck.legality_violations(&format.rules),
+      unmatched
+    }
+  }
+
+  fn maindeck_ok(&self) -> bool {
+    self.maindeck_price <= self.maindeck_limit
+  }
+
+  fn sideboard_ok(&self) -> bool {
+    self.sideboard_price <= self.sideboard_limit
+  }
+
+  pub fn is_accepted(&self) -> bool {
+    self.maindeck_ok() && self.sideboard_ok() && self.violations.is_empty() && self.unmatched.is_empty()
+  }
+
+  pub fn headline(&self) -> &'static str {
+    match (self.maindeck_ok(), self.sideboard_ok(), self.violations.is_empty(), self.unmatched.is_empty()) {
+      (true, true, true, true) => "Deck accepted!",
+      (true, true, _, false) => "Deck error! Some cards could not be priced.",
+      (true, true, false, true) => "Deck error! Deck is not legal.",
+      (true, false, _, _) => "Deck error! Sideboard overpriced.",
+      (false, true, _, _) => "Deck error! Maindeck overpriced.",
+      _ => "Deck error! Maindeck and sideboard overpriced."
+    }
+  }
+
+  // Unmatched cards make the totals a lower bound
+  fn price_prefix(&self) -> &'static str {
+    if self.maindeck_ok() && self.sideboard_ok() && !self.unmatched.is_empty() { "at least " } else { "" }
+  }
+
+  // Violations and unmatched cards, with the emoji the text reply uses for each
+  pub fn problems(&self) -> Vec<String> {
+    self.violations.iter().map(|violation| format!(":warning: {}", violation))
+      .chain(self.unmatched.iter().map(|line| format!(":question: {}", line)))
+      .collect()
+  }
+
+  // Shown as "12.34 of 20.00"
+  pub fn board_total(&self, price: Cents, limit: Cents) -> String {
+    format!("{}{} of {}", self.price_prefix(), format_cents(price), format_cents(limit))
+  }
+
+  // Negative amounts are shown as how far over the limit a board is
+  pub fn remaining_budget(&self) -> String {
+    let remaining = |price: Cents, limit: Cents| if price <= limit {
+      format_cents(limit - price)
+    } else {
+      format!("-{}", format_cents(price - limit))
+    };
+
+    format!(
+      "Maindeck: {}\nSideboard: {}",
+      remaining(self.maindeck_price, self.maindeck_limit), remaining(self.sideboard_price, self.sideboard_limit)
+    )
+  }
+
+  // The plain text reply, for channels where the bot cannot send embeds
+  pub fn to_text(&self) -> String {
+    let emoji = if self.is_accepted() { ":white_check_mark:" } else { ":x:" };
+    let prefix = self.price_prefix();
+
+    let mut text = format!("{} {}", emoji, self.headline());
+    if self.is_accepted() {
+      text += &format!("\nDeck hash: {}", self.hash);
+    }
+
+    text += &format!(
+      "\nMaindeck price: {}{}\nSideboard price: {}{}",
+      prefix, format_cents(self.maindeck_price), prefix, format_cents(self.sideboard_price)
+    );
+
+    for problem in self.problems() {
+      text += &format!("\n{}", problem);
+    }
+
+    text
+  }
+}
+
+#[cfg(test)]
+fn priced_deck(deck_text: &str, prices: &[(&str, Cents)]) -> Deck {
+  let mut deck = Deck::from_goldfish_block(String::from("10108"), String::from(deck_text));
+  let entries = prices.iter().map(|(name, price)| test_source(name, Some(*price), None)).collect();
+
+  deck.update_pricing(entries, &PricePolicy::default());
+  deck
+}
+
+#[test]
+fn test_accepted_verdict() {
+  let deck = priced_deck("4 Treasure Hunt\r\n56 Island\r\n\r\n3 Negate", &[("Treasure Hunt", 25), ("Negate", 10)]);
+  let verdict = Verdict::new(&deck, &Format::paper_dreadful(), Vec::new());
+
+  assert!(verdict.is_accepted());
+  assert_eq!(verdict.board_total(verdict.maindeck_price, verdict.maindeck_limit), "1.00 of 20.00");
+  assert_eq!(verdict.remaining_budget(), "Maindeck: 19.00\nSideboard: 4.70");
+  assert_eq!(verdict.to_text(), format!(
+    ":white_check_mark: Deck accepted!\nDeck hash: {}\nMaindeck price: 1.00\nSideboard price: 0.30", deck.to_hash()
+  ));
+}
+
+#[test]
+fn test_rejected_verdict() {
+  let deck = priced_deck("4 Treasure Hunt\r\n56 Island\r\n\r\n3 Negate", &[("Treasure Hunt", 600), ("Negate", 10)]);
+  let verdict = Verdict::new(&deck, &Format::paper_dreadful(), Vec::new());
+
+  assert!(!verdict.is_accepted());
+  assert_eq!(verdict.remaining_budget(), "Maindeck: -4.00\nSideboard: 4.70");
+  assert_eq!(verdict.to_text(), ":x: Deck error! Maindeck overpriced.\nMaindeck price: 24.00\nSideboard price: 0.30");
+}
+
+#[test]
+fn test_unmatched_verdict() {
+  let deck = priced_deck("4 Treasure Hunt\r\n4 Tresure Hunt\r\n52 Island", &[("Treasure Hunt", 25)]);
+  let unmatched = vec![String::from("Tresure Hunt could not be priced. Did you mean Treasure Hunt?")];
+  let verdict = Verdict::new(&deck, &Format::paper_dreadful(), unmatched);
+
+  assert_eq!(verdict.headline(), "Deck error! Some cards could not be priced.");
+  assert_eq!(verdict.to_text(), concat!(
+    ":x: Deck error! Some cards could not be priced.\nMaindeck price: at least 1.00\nSideboard price: at least 0.00\n",
+    ":question: Tresure Hunt could not be priced. Did you mean Treasure Hunt?"
+  ));
+}